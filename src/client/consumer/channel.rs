@@ -2,30 +2,74 @@ use async_std::sync::Arc;
 
 use log::info;
 
-use lapin::options::{BasicQosOptions, QueueDeclareOptions};
-use lapin::{types::FieldTable, Channel as LapinChannel, Connection, Error as LapinError, Queue};
+use lapin::options::{
+    BasicQosOptions, ConfirmSelectOptions, ExchangeDeclareOptions, QueueBindOptions,
+    QueueDeclareOptions,
+};
+use lapin::types::{AMQPValue, FieldTable, LongString, ShortString};
+use lapin::{Channel as LapinChannel, Connection, Error as LapinError, ExchangeKind, Queue};
 
+use crate::config::queue::binding::{Argument, Arguments};
 use crate::config::queue::config::QueueConfig;
+use crate::config::queue::retry_queue_name;
+use crate::config::ExchangeConfig;
+
+const CLOSE_REPLY_CODE: u16 = 200;
 
 type ChannelResult = Result<(LapinChannel, Queue), LapinError>;
 
 pub struct Channel {}
 
 impl Channel {
+    pub async fn declare_exchanges(
+        connection: Arc<Connection>,
+        exchanges: &[ExchangeConfig],
+    ) -> Result<(), LapinError> {
+        if exchanges.is_empty() {
+            return Ok(());
+        }
+
+        let channel = connection.create_channel().await?;
+        for exchange in exchanges {
+            channel
+                .exchange_declare(
+                    &exchange.name,
+                    Self::exchange_kind(exchange),
+                    Self::exchange_options(exchange),
+                    Self::arguments(&exchange.arguments),
+                )
+                .await?;
+
+            info!("Exchange \"{}\" declared", exchange.name);
+        }
+
+        channel.close(CLOSE_REPLY_CODE, "OK").await
+    }
+
     pub async fn get_queue<S: AsRef<str>>(
         connection: Arc<Connection>,
         queue: QueueConfig,
         prefix: S,
+        exchanges: &[ExchangeConfig],
     ) -> ChannelResult {
         let channel = connection.create_channel().await?;
         channel
             .basic_qos(
-                queue.prefetch_count.unwrap_or(1) as u16,
+                // A batch can't be filled, and the messages can't be processed
+                // concurrently, with less unacknowledged messages.
+                queue
+                    .prefetch_count
+                    .unwrap_or(1)
+                    .max(queue.batch_limit() as i32)
+                    .max(queue.concurrency_limit() as i32) as u16,
                 BasicQosOptions {
                     ..Default::default()
                 },
             )
             .await?;
+        channel
+            .confirm_select(ConfirmSelectOptions::default())
+            .await?;
 
         info!(
             "[{}] Created channel with id: {}",
@@ -33,9 +77,76 @@ impl Channel {
             channel.id()
         );
 
-        let queue = channel
+        if let Some(ref results_exchange) = queue.results_exchange {
+            // An exchange listed in the configuration has already been declared with its own
+            // type and options, so here it's only checked to exist.
+            let configured = exchanges
+                .iter()
+                .any(|exchange| &exchange.name == results_exchange);
+            channel
+                .exchange_declare(
+                    results_exchange,
+                    ExchangeKind::Topic,
+                    ExchangeDeclareOptions {
+                        passive: configured,
+                        durable: true,
+                        ..Default::default()
+                    },
+                    FieldTable::default(),
+                )
+                .await?;
+        }
+
+        if let Some(ref dead_letter_queue) = queue.dead_letter_queue {
+            channel
+                .queue_declare(
+                    dead_letter_queue,
+                    QueueDeclareOptions {
+                        durable: true,
+                        auto_delete: false,
+                        ..Default::default()
+                    },
+                    FieldTable::default(),
+                )
+                .await?;
+        }
+
+        let queue_name = format!("{}{}", prefix.as_ref(), queue.queue_name);
+
+        if queue.has_delayed_retry() {
+            for delay in queue.retry_delays() {
+                // Expired messages are dead lettered back to the main queue.
+                let mut arguments = FieldTable::default();
+                arguments.insert(
+                    ShortString::from("x-message-ttl"),
+                    AMQPValue::LongLongInt(delay as i64),
+                );
+                arguments.insert(
+                    ShortString::from("x-dead-letter-exchange"),
+                    AMQPValue::LongString(LongString::from("")),
+                );
+                arguments.insert(
+                    ShortString::from("x-dead-letter-routing-key"),
+                    AMQPValue::LongString(LongString::from(queue_name.as_str())),
+                );
+
+                channel
+                    .queue_declare(
+                        &retry_queue_name(&queue_name, delay),
+                        QueueDeclareOptions {
+                            durable: true,
+                            auto_delete: false,
+                            ..Default::default()
+                        },
+                        arguments,
+                    )
+                    .await?;
+            }
+        }
+
+        let declared = channel
             .queue_declare(
-                &format!("{}{}", prefix.as_ref(), queue.queue_name),
+                &queue_name,
                 QueueDeclareOptions {
                     durable: true,
                     auto_delete: false,
@@ -45,6 +156,63 @@ impl Channel {
             )
             .await?;
 
-        Ok((channel, queue))
+        if let Some(ref bindings) = queue.bindings {
+            for binding in bindings.bindings() {
+                for routing_key in binding.routing_keys() {
+                    channel
+                        .queue_bind(
+                            &queue_name,
+                            &binding.exchange,
+                            routing_key,
+                            QueueBindOptions::default(),
+                            Self::arguments(&binding.arguments),
+                        )
+                        .await?;
+
+                    info!(
+                        "[{}] Queue bound to \"{}\" with routing key \"{}\"",
+                        queue.queue_name, binding.exchange, routing_key
+                    );
+                }
+            }
+        }
+
+        Ok((channel, declared))
+    }
+
+    pub fn exchange_kind(exchange: &ExchangeConfig) -> ExchangeKind {
+        match exchange.kind.as_deref().unwrap_or("direct") {
+            "direct" => ExchangeKind::Direct,
+            "fanout" => ExchangeKind::Fanout,
+            "headers" => ExchangeKind::Headers,
+            "topic" => ExchangeKind::Topic,
+            kind => ExchangeKind::Custom(kind.to_string()),
+        }
+    }
+
+    pub fn exchange_options(exchange: &ExchangeConfig) -> ExchangeDeclareOptions {
+        ExchangeDeclareOptions {
+            durable: exchange.durable.unwrap_or(true),
+            auto_delete: exchange.auto_delete.unwrap_or(false),
+            ..Default::default()
+        }
+    }
+
+    pub fn arguments(arguments: &Arguments) -> FieldTable {
+        let mut table = FieldTable::default();
+        for (name, value) in arguments.values() {
+            table.insert(
+                ShortString::from(name.as_str()),
+                match value {
+                    Argument::Bool(value) => AMQPValue::Boolean(*value),
+                    Argument::Integer(value) => AMQPValue::LongLongInt(*value),
+                    Argument::String(value) => {
+                        AMQPValue::LongString(LongString::from(value.as_str()))
+                    }
+                },
+            );
+        }
+
+        table
     }
 }
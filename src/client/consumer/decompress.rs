@@ -0,0 +1,76 @@
+use std::fmt;
+use std::io::{self, Read};
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+
+const GZIP: &str = "gzip";
+const X_GZIP: &str = "x-gzip";
+const DEFLATE: &str = "deflate";
+const ZSTD: &str = "zstd";
+const BROTLI: &str = "br";
+
+const BROTLI_BUFFER: usize = 4096;
+
+#[derive(Debug)]
+pub enum DecompressError {
+    TooLarge(usize),
+    IoError(io::Error),
+}
+
+impl fmt::Display for DecompressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecompressError::TooLarge(limit) => {
+                write!(f, "decompressed body larger than {} bytes", limit)
+            }
+            DecompressError::IoError(e) => write!(f, "invalid compressed body: {}", e),
+        }
+    }
+}
+
+type DecompressResult<T> = Result<T, DecompressError>;
+
+pub fn is_compressed<S: AsRef<str>>(content_encoding: S) -> bool {
+    matches!(
+        content_encoding.as_ref().trim().to_lowercase().as_str(),
+        GZIP | X_GZIP | DEFLATE | ZSTD | BROTLI
+    )
+}
+
+pub fn decompress<S: AsRef<str>>(
+    content_encoding: S,
+    data: &[u8],
+    limit: usize,
+) -> DecompressResult<Vec<u8>> {
+    match content_encoding.as_ref().trim().to_lowercase().as_str() {
+        GZIP | X_GZIP => read(GzDecoder::new(data), limit),
+        DEFLATE => read(ZlibDecoder::new(data), limit),
+        ZSTD => read(
+            zstd::stream::read::Decoder::new(data).map_err(DecompressError::IoError)?,
+            limit,
+        ),
+        BROTLI => read(
+            brotli_decompressor::Decompressor::new(data, BROTLI_BUFFER),
+            limit,
+        ),
+        // Other encodings (e.g. "identity", or a charset set by mistake) are not compressed.
+        _ => Ok(data.to_vec()),
+    }
+}
+
+fn read<R: Read>(decoder: R, limit: usize) -> DecompressResult<Vec<u8>> {
+    let mut decompressed = vec![];
+
+    // One more byte than the limit tells a body of exactly `limit` bytes from a larger one,
+    // without decompressing the rest of it.
+    decoder
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(DecompressError::IoError)?;
+
+    if decompressed.len() > limit {
+        return Err(DecompressError::TooLarge(limit));
+    }
+
+    Ok(decompressed)
+}
@@ -0,0 +1,167 @@
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use lapin::message::Delivery;
+
+use crate::client::consumer::decompress;
+use crate::client::consumer::metadata::Metadata;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+const COMPACT_THRESHOLD: usize = 1000;
+// The consumers of other processes sharing the store hold its lock only briefly.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// The headers changed by the broker, or by the consumer itself, on each redelivery.
+const VOLATILE_HEADERS: [&str; 3] = ["x-death", "x-delivery-count", "x-retry-count"];
+
+// A SQLite database shared by the consumers of the queue, also across processes. The
+// operations are blocking, so they are meant to run on the blocking threads.
+#[derive(Clone)]
+pub struct DedupStore {
+    connection: Arc<Mutex<Connection>>,
+    window: i64,
+    inserted: Arc<AtomicUsize>,
+}
+
+impl DedupStore {
+    pub fn open<P: AsRef<Path>>(path: P, window: u64, now: i64) -> io::Result<Self> {
+        let connection = Connection::open(path).map_err(Self::error)?;
+        connection.busy_timeout(BUSY_TIMEOUT).map_err(Self::error)?;
+        connection
+            .execute_batch(
+                "PRAGMA journal_mode = WAL;
+                CREATE TABLE IF NOT EXISTS acked (key TEXT PRIMARY KEY, acked INTEGER NOT NULL);",
+            )
+            .map_err(Self::error)?;
+
+        let store = DedupStore {
+            connection: Arc::new(Mutex::new(connection)),
+            window: window as i64,
+            inserted: Arc::new(AtomicUsize::new(0)),
+        };
+        store.compact(now)?;
+
+        Ok(store)
+    }
+
+    pub fn contains<S: AsRef<str>>(&self, key: S, now: i64) -> io::Result<bool> {
+        let acked = self
+            .connection()
+            .query_row(
+                "SELECT acked FROM acked WHERE key = ?1",
+                params![key.as_ref()],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .map_err(Self::error)?;
+
+        Ok(acked
+            .map(|acked| now - acked < self.window)
+            .unwrap_or(false))
+    }
+
+    pub fn insert<S: AsRef<str>>(&self, key: S, now: i64) -> io::Result<()> {
+        self.connection()
+            .execute(
+                "INSERT OR REPLACE INTO acked (key, acked) VALUES (?1, ?2)",
+                params![key.as_ref(), now],
+            )
+            .map_err(Self::error)?;
+
+        // The expired keys are deleted every thousand insertions.
+        if (self.inserted.fetch_add(1, Ordering::Relaxed) + 1) % COMPACT_THRESHOLD == 0 {
+            self.compact(now)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> io::Result<usize> {
+        self.connection()
+            .query_row("SELECT COUNT(*) FROM acked", [], |row| row.get::<_, i64>(0))
+            .map(|count| count as usize)
+            .map_err(Self::error)
+    }
+
+    pub fn is_empty(&self) -> io::Result<bool> {
+        self.len().map(|len| len == 0)
+    }
+
+    fn compact(&self, now: i64) -> io::Result<()> {
+        self.connection()
+            .execute(
+                "DELETE FROM acked WHERE acked <= ?1",
+                params![now - self.window],
+            )
+            .map(|_| ())
+            .map_err(Self::error)
+    }
+
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn error(e: rusqlite::Error) -> io::Error {
+        io::Error::other(e)
+    }
+}
+
+pub fn key(delivery: &Delivery, hash_only: bool, decompress_limit: usize) -> String {
+    match delivery.properties.message_id() {
+        Some(message_id) if !hash_only => format!("id:{}", message_id),
+        _ => format!("hash:{:016x}", hash(delivery, decompress_limit)),
+    }
+}
+
+fn hash(delivery: &Delivery, decompress_limit: usize) -> u64 {
+    let mut hash = FNV_OFFSET;
+    let mut write = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+        // A separator, so that moving bytes between fields changes the hash.
+        hash ^= 0xff;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    };
+
+    // The body is hashed once decompressed, so that the same message compressed in
+    // different ways has the same key.
+    match delivery.properties.content_encoding() {
+        Some(encoding) if decompress::is_compressed(encoding.as_str()) => {
+            match decompress::decompress(encoding.as_str(), &delivery.data, decompress_limit) {
+                Ok(data) => write(&data),
+                Err(_) => write(&delivery.data),
+            }
+        }
+        _ => write(&delivery.data),
+    }
+
+    if let Some(headers) = delivery.properties.headers() {
+        let mut headers = headers
+            .inner()
+            .iter()
+            .filter(|(name, _)| !VOLATILE_HEADERS.contains(&name.as_str()))
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    Metadata::value_to_string(value).unwrap_or_default(),
+                )
+            })
+            .collect::<Vec<(String, String)>>();
+        headers.sort();
+
+        for (name, value) in headers {
+            write(name.as_bytes());
+            write(value.as_bytes());
+        }
+    }
+
+    hash
+}
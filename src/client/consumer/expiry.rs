@@ -0,0 +1,67 @@
+use chrono::DateTime;
+
+use lapin::message::Delivery;
+
+use crate::client::consumer::metadata::Metadata;
+use crate::config::queue::config::QueueConfig;
+
+pub const DEADLINE_HEADER: &str = "x-deadline";
+
+// Timestamps after this value (year 5138 in seconds) are taken as milliseconds.
+const MILLISECONDS_THRESHOLD: i64 = 100_000_000_000;
+const MS_MULTIPLIER: i64 = 1000;
+
+pub fn parse_time<S: AsRef<str>>(value: S) -> Option<i64> {
+    let value = value.as_ref().trim();
+
+    match value.parse::<i64>() {
+        Ok(time) if time.abs() >= MILLISECONDS_THRESHOLD => Some(time),
+        Ok(time) => Some(time * MS_MULTIPLIER),
+        Err(_) => DateTime::parse_from_rfc3339(value)
+            .ok()
+            .map(|time| time.timestamp_millis()),
+    }
+}
+
+pub fn stale(queue_config: &QueueConfig, delivery: &Delivery, now: i64) -> Option<String> {
+    if let Some(deadline) = header(delivery, DEADLINE_HEADER) {
+        if now > deadline {
+            return Some(format!(
+                "deadline exceeded by {} milliseconds",
+                now - deadline
+            ));
+        }
+    }
+
+    let max_age = queue_config.max_age? as i64 * MS_MULTIPLIER;
+    let created = match queue_config.age_header {
+        Some(ref name) => header(delivery, name),
+        None => delivery.properties.timestamp().map(|timestamp| {
+            let timestamp = timestamp as i64;
+            if timestamp >= MILLISECONDS_THRESHOLD {
+                timestamp
+            } else {
+                timestamp * MS_MULTIPLIER
+            }
+        }),
+    }?;
+
+    let age = now - created;
+    if age > max_age {
+        Some(format!("{} milliseconds old", age))
+    } else {
+        None
+    }
+}
+
+fn header<S: AsRef<str>>(delivery: &Delivery, name: S) -> Option<i64> {
+    delivery
+        .properties
+        .headers()
+        .as_ref()?
+        .inner()
+        .iter()
+        .find(|(header, _)| header.as_str().eq_ignore_ascii_case(name.as_ref()))
+        .and_then(|(_, value)| Metadata::value_to_string(value))
+        .and_then(parse_time)
+}
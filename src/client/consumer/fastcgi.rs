@@ -0,0 +1,255 @@
+use std::fmt;
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UnixStream};
+
+const VERSION: u8 = 1;
+const REQUEST_ID: u16 = 1;
+const RESPONDER: u16 = 1;
+const MAX_CONTENT: usize = 65535;
+const HEADER_LENGTH: usize = 8;
+
+const BEGIN_REQUEST: u8 = 1;
+const END_REQUEST: u8 = 3;
+const PARAMS: u8 = 4;
+const STDIN: u8 = 5;
+const STDOUT: u8 = 6;
+const STDERR: u8 = 7;
+
+const UNIX_PREFIX: &str = "unix:";
+const TCP_PREFIX: &str = "tcp://";
+const DEFAULT_STATUS: u16 = 200;
+
+#[derive(Debug)]
+pub enum FastCgiError {
+    InvalidAddress(String),
+    InvalidResponse(String),
+    IoError(io::Error),
+}
+
+impl fmt::Display for FastCgiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FastCgiError::InvalidAddress(e) => write!(f, "invalid FastCGI address: {}", e),
+            FastCgiError::InvalidResponse(e) => write!(f, "invalid FastCGI response: {}", e),
+            FastCgiError::IoError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+type FastCgiResult<T> = Result<T, FastCgiError>;
+
+#[derive(Debug)]
+pub struct FastCgiResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl FastCgiResponse {
+    pub fn header<S: AsRef<str>>(&self, name: S) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name.as_ref()))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn parse(mut response: &[u8]) -> FastCgiResult<Self> {
+        let invalid = |e: &str| FastCgiError::InvalidResponse(e.into());
+
+        let mut stdout = vec![];
+        let mut stderr = vec![];
+        let mut ended = false;
+        while response.len() >= HEADER_LENGTH {
+            let kind = response[1];
+            let length = u16::from_be_bytes([response[4], response[5]]) as usize;
+            let padding = response[6] as usize;
+            let content = response
+                .get(HEADER_LENGTH..HEADER_LENGTH + length)
+                .ok_or_else(|| invalid("truncated record"))?;
+
+            match kind {
+                STDOUT => stdout.extend_from_slice(content),
+                STDERR => stderr.extend_from_slice(content),
+                END_REQUEST => {
+                    ended = true;
+
+                    break;
+                }
+                _ => {}
+            }
+
+            response = response
+                .get(HEADER_LENGTH + length + padding..)
+                .ok_or_else(|| invalid("truncated record"))?;
+        }
+
+        if !ended {
+            return Err(invalid("missing end of request"));
+        }
+
+        // The response is a CGI response: headers, an empty line and the body.
+        let (head, body) = match stdout.windows(4).position(|window| window == b"\r\n\r\n") {
+            Some(end) => (&stdout[..end], &stdout[end + 4..]),
+            None => match stdout.windows(2).position(|window| window == b"\n\n") {
+                Some(end) => (&stdout[..end], &stdout[end + 2..]),
+                None => (&stdout[..0], &stdout[..]),
+            },
+        };
+
+        let headers = String::from_utf8_lossy(head)
+            .lines()
+            .filter_map(|line| {
+                line.find(':').map(|position| {
+                    (
+                        line[..position].trim().to_string(),
+                        line[position + 1..].trim().to_string(),
+                    )
+                })
+            })
+            .collect::<Vec<(String, String)>>();
+
+        let mut parsed = FastCgiResponse {
+            status: DEFAULT_STATUS,
+            headers,
+            body: body.to_vec(),
+            stderr,
+        };
+        if let Some(status) = parsed.header("status") {
+            parsed.status = status
+                .split_whitespace()
+                .next()
+                .and_then(|status| status.parse::<u16>().ok())
+                .ok_or_else(|| invalid("invalid status"))?;
+        }
+
+        Ok(parsed)
+    }
+}
+
+fn record(kind: u8, content: &[u8], request: &mut Vec<u8>) {
+    let padding = (8 - content.len() % 8) % 8;
+
+    request.extend_from_slice(&[VERSION, kind]);
+    request.extend_from_slice(&REQUEST_ID.to_be_bytes());
+    request.extend_from_slice(&(content.len() as u16).to_be_bytes());
+    request.extend_from_slice(&[padding as u8, 0]);
+    request.extend_from_slice(content);
+    request.extend_from_slice(&[0; 8][..padding]);
+}
+
+fn stream(kind: u8, content: &[u8], request: &mut Vec<u8>) {
+    for chunk in content.chunks(MAX_CONTENT) {
+        record(kind, chunk, request);
+    }
+
+    // An empty record closes the stream.
+    record(kind, &[], request);
+}
+
+fn length(length: usize, params: &mut Vec<u8>) {
+    if length < 128 {
+        params.push(length as u8);
+    } else {
+        params.extend_from_slice(&(length as u32 | 0x8000_0000).to_be_bytes());
+    }
+}
+
+fn encode(params: &[(String, String)], body: &[u8]) -> Vec<u8> {
+    let mut request = vec![];
+
+    let mut begin = RESPONDER.to_be_bytes().to_vec();
+    begin.extend_from_slice(&[0; 6]);
+    record(BEGIN_REQUEST, &begin, &mut request);
+
+    let mut encoded = vec![];
+    for (name, value) in params {
+        length(name.len(), &mut encoded);
+        length(value.len(), &mut encoded);
+        encoded.extend_from_slice(name.as_bytes());
+        encoded.extend_from_slice(value.as_bytes());
+    }
+    stream(PARAMS, &encoded, &mut request);
+    stream(STDIN, body, &mut request);
+
+    request
+}
+
+async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    request: &[u8],
+    limit: usize,
+) -> FastCgiResult<FastCgiResponse> {
+    stream
+        .write_all(request)
+        .await
+        .map_err(FastCgiError::IoError)?;
+
+    // One byte more than the limit tells a response of exactly `limit` bytes from a larger one.
+    let mut response = vec![];
+    (&mut stream)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut response)
+        .await
+        .map_err(FastCgiError::IoError)?;
+    if response.len() > limit {
+        return Err(FastCgiError::InvalidResponse(format!(
+            "response larger than {} bytes",
+            limit
+        )));
+    }
+
+    FastCgiResponse::parse(&response)
+}
+
+pub async fn request<S: AsRef<str>>(
+    address: S,
+    script_filename: S,
+    params: &[(String, String)],
+    body: &[u8],
+    limit: usize,
+) -> FastCgiResult<FastCgiResponse> {
+    let mut all = vec![
+        ("GATEWAY_INTERFACE".to_string(), "FastCGI/1.0".to_string()),
+        ("SERVER_PROTOCOL".into(), "HTTP/1.1".into()),
+        ("REQUEST_METHOD".into(), "POST".into()),
+        ("SCRIPT_FILENAME".into(), script_filename.as_ref().into()),
+        ("SCRIPT_NAME".into(), script_filename.as_ref().into()),
+        ("REQUEST_URI".into(), script_filename.as_ref().into()),
+        ("QUERY_STRING".into(), String::new()),
+        ("CONTENT_LENGTH".into(), body.len().to_string()),
+    ];
+    all.extend_from_slice(params);
+
+    let request = encode(&all, body);
+    let address = address.as_ref();
+
+    if let Some(path) = address.strip_prefix(UNIX_PREFIX) {
+        let path = path.trim_start_matches("//");
+
+        exchange(
+            UnixStream::connect(path)
+                .await
+                .map_err(FastCgiError::IoError)?,
+            &request,
+            limit,
+        )
+        .await
+    } else {
+        let host = address.strip_prefix(TCP_PREFIX).unwrap_or(address);
+        if host.is_empty() {
+            return Err(FastCgiError::InvalidAddress(address.into()));
+        }
+
+        exchange(
+            TcpStream::connect(host)
+                .await
+                .map_err(FastCgiError::IoError)?,
+            &request,
+            limit,
+        )
+        .await
+    }
+}
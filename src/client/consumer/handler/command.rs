@@ -0,0 +1,326 @@
+use std::io;
+
+use async_std::sync::{Arc, RwLock};
+
+use log::{error, info};
+
+use futures::future::{BoxFuture, FutureExt};
+
+use serde_json::Value;
+
+use lapin::message::Delivery;
+
+use base64::encode as base64_encode;
+
+use crate::client::consumer::handler::report::{Report, TIMEOUT_REASON};
+use crate::client::consumer::handler::{Handled, HandlerContext, MessageHandler};
+use crate::client::consumer::message::command::{CommandError, CommandResult, MessageCommand};
+use crate::client::consumer::message::payload::Payload;
+use crate::client::consumer::output::Capture;
+use crate::config::queue::config::QueueConfig;
+use crate::config::queue::outcome::Outcome;
+use crate::config::queue::{Charset, Queue, RetryType};
+
+const BATCH_SIZE_VARIABLE: &str = "AMQP_BATCH_SIZE";
+
+pub struct CommandHandler {
+    queue: Arc<RwLock<Queue>>,
+}
+
+impl CommandHandler {
+    pub fn new(queue: Arc<RwLock<Queue>>) -> Self {
+        CommandHandler { queue }
+    }
+
+    async fn execute(&self, context: &HandlerContext<'_>) -> io::Result<Handled> {
+        let index = context.index;
+        let queue_config = context.queue_config;
+        let delivery = context.delivery;
+
+        let charset = self.queue.write().await.get_charset(queue_config.id);
+        let payload = Payload::new(&delivery.data, charset, queue_config.base64);
+        let msg = payload.argument(queue_config.base64);
+
+        let delivery_mode = self.queue.write().await.get_delivery_mode(queue_config.id);
+        let cmd = self.queue.write().await.get_command(queue_config.id);
+
+        let mut message_command = match MessageCommand::new(
+            &cmd,
+            queue_config,
+            delivery_mode,
+            &payload,
+            delivery,
+        ) {
+            Ok(message_command) => message_command,
+            Err(CommandError::IoError(e)) => {
+                return Ok(Self::io_error(index, queue_config, &cmd, &msg, e));
+            }
+            Err(CommandError::UndecodableBody) => {
+                error!(
+                    "[{}] Message body can't be decoded for the command \"{}\" on consumer #{}",
+                    queue_config.queue_name, cmd, index
+                );
+
+                return Ok(Handled::new(
+                    Outcome::Reject,
+                    Report::new(
+                        format!(
+                            "Command \"{}\" not executed on consumer #{} with an undecodable message",
+                            cmd, index
+                        ),
+                        cmd.clone(),
+                        CommandError::UndecodableBody.to_string(),
+                    ),
+                ));
+            }
+            Err(e) => {
+                error!(
+                    "[{}] Error ({}) preparing the command \"{}\" on consumer #{}",
+                    queue_config.queue_name, e, cmd, index
+                );
+
+                return Ok(Handled::new(
+                    queue_config.outcomes.clone().unwrap_or_default().error(),
+                    Report::new(
+                        format!(
+                            "Command \"{}\" not executed on consumer #{} with message \"{}\"",
+                            cmd, index, msg
+                        ),
+                        cmd.clone(),
+                        e.to_string(),
+                    ),
+                ));
+            }
+        };
+
+        message_command.command.envs(context.variables());
+
+        let reply = self.replies(queue_config, [delivery]).await;
+
+        Ok(self
+            .run(index, queue_config, message_command, &msg, reply)
+            .await)
+    }
+
+    pub async fn execute_batch(
+        &self,
+        index: i32,
+        queue_config: &QueueConfig,
+        deliveries: &[Delivery],
+    ) -> io::Result<Handled> {
+        let cmd = self.queue.write().await.get_command(queue_config.id);
+        let charset = self.queue.write().await.get_charset(queue_config.id);
+        let msg = format!("batch of {} messages", deliveries.len());
+
+        // Each body is a JSON line: JSON bodies are compacted, the others are encoded
+        // as JSON strings.
+        let mut lines = vec![];
+        for delivery in deliveries {
+            let body = if queue_config.base64 {
+                Value::String(base64_encode(&delivery.data))
+            } else {
+                let text = charset
+                    .as_ref()
+                    .unwrap_or(&Charset::Utf8)
+                    .decode(&delivery.data)
+                    .unwrap_or_else(|| String::from_utf8_lossy(&delivery.data).to_string());
+
+                serde_json::from_str::<Value>(&text).unwrap_or(Value::String(text))
+            };
+
+            lines.extend_from_slice(body.to_string().as_bytes());
+            lines.push(b'\n');
+        }
+
+        let mut message_command = match MessageCommand::batch(&cmd, queue_config, lines) {
+            Ok(message_command) => message_command,
+            Err(CommandError::IoError(e)) => {
+                return Ok(Self::io_error(index, queue_config, &cmd, &msg, e));
+            }
+            Err(e) => {
+                error!(
+                    "[{}] Error ({}) preparing the command \"{}\" on consumer #{}",
+                    queue_config.queue_name, e, cmd, index
+                );
+
+                return Ok(Handled::new(
+                    queue_config.outcomes.clone().unwrap_or_default().error(),
+                    Report::new(
+                        format!(
+                            "Command \"{}\" not executed on consumer #{} with {}",
+                            cmd, index, msg
+                        ),
+                        cmd.clone(),
+                        e.to_string(),
+                    ),
+                ));
+            }
+        };
+
+        message_command
+            .command
+            .env(BATCH_SIZE_VARIABLE, deliveries.len().to_string());
+
+        let reply = self.replies(queue_config, deliveries.iter()).await;
+
+        Ok(self
+            .run(index, queue_config, message_command, &msg, reply)
+            .await)
+    }
+
+    // The output limit doesn't apply to the standard output published as a reply, which
+    // would be silently truncated otherwise.
+    async fn replies<'a, I: IntoIterator<Item = &'a Delivery>>(
+        &self,
+        queue_config: &QueueConfig,
+        deliveries: I,
+    ) -> bool {
+        self.queue
+            .write()
+            .await
+            .get_reply_mode(queue_config.id)
+            .is_some()
+            && deliveries
+                .into_iter()
+                .any(|delivery| delivery.properties.reply_to().is_some())
+    }
+
+    // The command can't be prepared, e.g. when the body file can't be written: the message
+    // is settled as a command that can't be executed.
+    fn io_error(
+        index: i32,
+        queue_config: &QueueConfig,
+        cmd: &str,
+        msg: &str,
+        e: io::Error,
+    ) -> Handled {
+        error!(
+            "[{}] Error {:?} preparing the command \"{}\" on consumer #{}",
+            queue_config.queue_name, e, cmd, index
+        );
+
+        Handled::new(
+            queue_config.outcomes.clone().unwrap_or_default().error(),
+            Report::new(
+                format!(
+                    "Command \"{}\" not executed on consumer #{} with message \"{}\"",
+                    cmd, index, msg
+                ),
+                cmd.to_string(),
+                e.to_string(),
+            ),
+        )
+    }
+
+    async fn run(
+        &self,
+        index: i32,
+        queue_config: &QueueConfig,
+        mut message_command: MessageCommand,
+        msg: &str,
+        reply: bool,
+    ) -> Handled {
+        info!(
+            "[{}] Executing command \"{}\" on consumer #{}",
+            queue_config.queue_name, message_command.human, index
+        );
+
+        let timeout = self
+            .queue
+            .write()
+            .await
+            .get_command_timeout(queue_config.id);
+        let kill_grace = self.queue.write().await.get_kill_grace(queue_config.id);
+        let outcomes = queue_config.outcomes.clone().unwrap_or_default();
+
+        let mut capture = Capture::for_queue(index, queue_config);
+        if reply {
+            capture = capture.keep_stdout();
+        }
+        capture.record("command", &message_command.human);
+
+        let (outcome, report) = match message_command
+            .execute(timeout, kill_grace, &capture)
+            .await
+        {
+            CommandResult::Output(Ok(output)) => {
+                let retry_type = self.queue.write().await.get_retry_type(queue_config.id);
+                let (outcome, description) = match retry_type {
+                    RetryType::Ignored => (
+                        Outcome::Ack,
+                        format!(
+                            "Command \"{}\" executed on consumer #{} and result ignored",
+                            message_command.human, index
+                        ),
+                    ),
+                    _ => (
+                        outcomes.exit(&output.status, queue_config.nack_code),
+                        if output.status.success() {
+                            format!(
+                                "Command \"{}\" succeeded on consumer #{}",
+                                message_command.human, index
+                            )
+                        } else {
+                            format!(
+                                "Command \"{}\" failed on consumer #{} with {}",
+                                message_command.human, index, output.status
+                            )
+                        },
+                    ),
+                };
+
+                (
+                    outcome,
+                    Report::new(
+                        description,
+                        message_command.human.clone(),
+                        output.status.to_string(),
+                    )
+                    .with_output(&output),
+                )
+            }
+            CommandResult::Output(Err(e)) => (
+                outcomes.error(),
+                Report::new(
+                    format!(
+                        "Error {:?} executing the command \"{}\" on consumer #{} with message \"{}\"",
+                        e, message_command.human, index, msg
+                    ),
+                    message_command.human.clone(),
+                    e.to_string(),
+                ),
+            ),
+            CommandResult::Timeout(killed) => (
+                outcomes.timeout(),
+                Report::new(
+                    format!(
+                        "Timeout occurred executing the command \"{}\" on consumer #{}, process {}, message \"{}\"",
+                        message_command.human,
+                        index,
+                        if killed { "killed" } else { "terminated" },
+                        msg
+                    ),
+                    message_command.human.clone(),
+                    TIMEOUT_REASON.into(),
+                ),
+            ),
+        };
+
+        capture.record("result", &report.reason);
+
+        if let Err(e) = message_command.close() {
+            error!(
+                "[{}] Error {:?} removing the body file of the command \"{}\" on consumer #{}",
+                queue_config.queue_name, e, message_command.human, index
+            );
+        }
+
+        Handled::new(outcome, report)
+    }
+}
+
+impl MessageHandler for CommandHandler {
+    fn handle<'a>(&'a self, context: &'a HandlerContext<'a>) -> BoxFuture<'a, io::Result<Handled>> {
+        self.execute(context).boxed()
+    }
+}
@@ -0,0 +1,112 @@
+use std::io;
+
+use tokio::time::{self, Duration};
+
+use async_std::sync::{Arc, RwLock};
+
+use log::info;
+
+use futures::future::{BoxFuture, FutureExt};
+
+use crate::client::consumer::fastcgi;
+use crate::client::consumer::handler::report::{Report, TIMEOUT_REASON};
+use crate::client::consumer::handler::{Handled, HandlerContext, MessageHandler};
+use crate::config::queue::outcome::Outcome;
+use crate::config::queue::{Queue, RetryType};
+
+const OUTCOME_HEADER: &str = "X-Consumer-Outcome";
+
+pub struct FastCgiHandler {
+    queue: Arc<RwLock<Queue>>,
+}
+
+impl FastCgiHandler {
+    pub fn new(queue: Arc<RwLock<Queue>>) -> Self {
+        FastCgiHandler { queue }
+    }
+
+    async fn request(&self, context: &HandlerContext<'_>) -> io::Result<Handled> {
+        let index = context.index;
+        let queue_config = context.queue_config;
+
+        let address = self.queue.write().await.get_url(queue_config.id);
+        let script_filename = queue_config.script_filename.clone().unwrap_or_default();
+        let timeout = self
+            .queue
+            .write()
+            .await
+            .get_command_timeout(queue_config.id);
+        let outcomes = queue_config.outcomes.clone().unwrap_or_default();
+
+        let mut params = context.variables();
+        params.push(("CONTENT_TYPE".into(), context.content_type()));
+
+        info!(
+            "[{}] Sending FastCGI request for \"{}\" to \"{}\" on consumer #{}",
+            queue_config.queue_name, script_filename, address, index
+        );
+
+        let (outcome, report) = match time::timeout(
+            Duration::from_millis(timeout),
+            fastcgi::request(
+                &address,
+                &script_filename,
+                &params,
+                &context.body(),
+                queue_config.output_limit(),
+            ),
+        )
+        .await
+        {
+            Ok(Ok(response)) => (
+                match self.queue.write().await.get_retry_type(queue_config.id) {
+                    RetryType::Ignored => Outcome::Ack,
+                    _ => response
+                        .header(OUTCOME_HEADER)
+                        .and_then(|outcome| outcome.parse::<Outcome>().ok())
+                        .unwrap_or_else(|| outcomes.status(response.status)),
+                },
+                Report::new(
+                    format!(
+                        "FastCGI request for \"{}\" answered with status {} on consumer #{}",
+                        script_filename, response.status, index
+                    ),
+                    script_filename.clone(),
+                    format!("FastCGI status: {}", response.status),
+                )
+                .with_stdout(response.body)
+                .with_stderr(response.stderr),
+            ),
+            Ok(Err(e)) => (
+                outcomes.error(),
+                Report::new(
+                    format!(
+                        "Error ({}) sending the FastCGI request for \"{}\" to \"{}\" on consumer #{}",
+                        e, script_filename, address, index
+                    ),
+                    script_filename.clone(),
+                    e.to_string(),
+                ),
+            ),
+            Err(_) => (
+                outcomes.timeout(),
+                Report::new(
+                    format!(
+                        "Timeout occurred sending the FastCGI request for \"{}\" to \"{}\" on consumer #{}",
+                        script_filename, address, index
+                    ),
+                    script_filename.clone(),
+                    TIMEOUT_REASON.into(),
+                ),
+            ),
+        };
+
+        Ok(Handled::new(outcome, report))
+    }
+}
+
+impl MessageHandler for FastCgiHandler {
+    fn handle<'a>(&'a self, context: &'a HandlerContext<'a>) -> BoxFuture<'a, io::Result<Handled>> {
+        self.request(context).boxed()
+    }
+}
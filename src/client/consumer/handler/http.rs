@@ -0,0 +1,103 @@
+use std::io;
+
+use tokio::time::{self, Duration};
+
+use async_std::sync::{Arc, RwLock};
+
+use log::info;
+
+use futures::future::{BoxFuture, FutureExt};
+
+use crate::client::consumer::handler::report::{Report, TIMEOUT_REASON};
+use crate::client::consumer::handler::{Handled, HandlerContext, MessageHandler};
+use crate::client::consumer::http;
+use crate::config::queue::outcome::Outcome;
+use crate::config::queue::{Queue, RetryType};
+
+pub struct HttpHandler {
+    queue: Arc<RwLock<Queue>>,
+}
+
+impl HttpHandler {
+    pub fn new(queue: Arc<RwLock<Queue>>) -> Self {
+        HttpHandler { queue }
+    }
+
+    async fn request(&self, context: &HandlerContext<'_>) -> io::Result<Handled> {
+        let index = context.index;
+        let queue_config = context.queue_config;
+
+        let url = self.queue.write().await.get_url(queue_config.id);
+        let timeout = self
+            .queue
+            .write()
+            .await
+            .get_command_timeout(queue_config.id);
+        let outcomes = queue_config.outcomes.clone().unwrap_or_default();
+
+        let mut headers = context
+            .variables()
+            .into_iter()
+            .map(|(name, value)| (format!("X-{}", name.replace('_', "-")), value))
+            .collect::<Vec<(String, String)>>();
+        headers.push(("Content-Type".into(), context.content_type()));
+
+        info!(
+            "[{}] Sending request to \"{}\" on consumer #{}",
+            queue_config.queue_name, url, index
+        );
+
+        let (outcome, report) = match time::timeout(
+            Duration::from_millis(timeout),
+            http::post(&url, &headers, &context.body(), queue_config.output_limit()),
+        )
+        .await
+        {
+            Ok(Ok(response)) => (
+                match self.queue.write().await.get_retry_type(queue_config.id) {
+                    RetryType::Ignored => Outcome::Ack,
+                    _ => outcomes.status(response.status),
+                },
+                Report::new(
+                    format!(
+                        "Request to \"{}\" answered with status {} on consumer #{}",
+                        url, response.status, index
+                    ),
+                    url.clone(),
+                    format!("HTTP status: {} {}", response.status, response.reason),
+                )
+                .with_stdout(response.body),
+            ),
+            Ok(Err(e)) => (
+                outcomes.error(),
+                Report::new(
+                    format!(
+                        "Error ({}) sending the request to \"{}\" on consumer #{}",
+                        e, url, index
+                    ),
+                    url.clone(),
+                    e.to_string(),
+                ),
+            ),
+            Err(_) => (
+                outcomes.timeout(),
+                Report::new(
+                    format!(
+                        "Timeout occurred sending the request to \"{}\" on consumer #{}",
+                        url, index
+                    ),
+                    url.clone(),
+                    TIMEOUT_REASON.into(),
+                ),
+            ),
+        };
+
+        Ok(Handled::new(outcome, report))
+    }
+}
+
+impl MessageHandler for HttpHandler {
+    fn handle<'a>(&'a self, context: &'a HandlerContext<'a>) -> BoxFuture<'a, io::Result<Handled>> {
+        self.request(context).boxed()
+    }
+}
@@ -0,0 +1,88 @@
+mod command;
+mod fastcgi;
+mod http;
+pub mod report;
+mod worker;
+
+pub use command::CommandHandler;
+pub use fastcgi::FastCgiHandler;
+pub use http::HttpHandler;
+pub use worker::WorkerHandler;
+
+use std::io;
+
+use futures::future::BoxFuture;
+
+use lapin::message::Delivery;
+
+use base64::encode as base64_encode;
+
+use crate::client::consumer::handler::report::Report;
+use crate::client::consumer::metadata::Metadata;
+use crate::config::queue::config::QueueConfig;
+use crate::config::queue::outcome::Outcome;
+
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+pub struct HandlerContext<'a> {
+    pub index: i32,
+    pub queue_name: String,
+    pub queue_config: &'a QueueConfig,
+    pub delivery: &'a Delivery,
+}
+
+impl<'a> HandlerContext<'a> {
+    pub fn variables(&self) -> Vec<(String, String)> {
+        Metadata::new(
+            &self.queue_name,
+            self.index,
+            self.delivery,
+            self.queue_config.env_headers.as_deref(),
+        )
+        .variables()
+        .to_vec()
+    }
+
+    pub fn content_type(&self) -> String {
+        match self.delivery.properties.content_type() {
+            Some(content_type) => content_type.to_string(),
+            None => DEFAULT_CONTENT_TYPE.into(),
+        }
+    }
+
+    pub fn body(&self) -> Vec<u8> {
+        if self.queue_config.base64 {
+            base64_encode(&self.delivery.data).into_bytes()
+        } else {
+            self.delivery.data.clone()
+        }
+    }
+}
+
+pub struct Handled {
+    pub outcome: Outcome,
+    pub report: Report,
+}
+
+impl Handled {
+    pub fn new(outcome: Outcome, report: Report) -> Self {
+        Handled { outcome, report }
+    }
+}
+
+impl From<Outcome> for Handled {
+    fn from(outcome: Outcome) -> Self {
+        Handled::new(
+            outcome,
+            Report::new(
+                format!("Message handled with outcome \"{}\"", outcome),
+                String::new(),
+                outcome.to_string(),
+            ),
+        )
+    }
+}
+
+pub trait MessageHandler: Send + Sync {
+    fn handle<'a>(&'a self, context: &'a HandlerContext<'a>) -> BoxFuture<'a, io::Result<Handled>>;
+}
@@ -0,0 +1,184 @@
+use std::process::Output;
+use std::time::Duration;
+
+use chrono::Utc;
+
+use serde_json::json;
+
+use lapin::message::Delivery;
+use lapin::types::{AMQPValue, FieldArray, FieldTable, LongString, ShortString};
+
+use crate::config::queue::outcome::Outcome;
+use crate::config::queue::ReplyMode;
+use crate::utils;
+
+// AMQP headers must stay small, whatever the output limit of the queue.
+pub const HEADER_OUTPUT_LIMIT: usize = 4096;
+pub const TIMEOUT_REASON: &str = "timeout";
+pub const INVALID_REASON: &str = "invalid payload";
+
+const HEADER_PREFIX: &str = "x-consumer-";
+
+pub struct Report {
+    pub description: String,
+    pub command: String,
+    pub reason: String,
+    pub exit_code: Option<i32>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub errors: Vec<String>,
+    pub duration: Duration,
+}
+
+impl Report {
+    pub fn new<S: Into<String>>(description: S, command: S, reason: S) -> Self {
+        Report {
+            description: description.into(),
+            command: command.into(),
+            reason: reason.into(),
+            exit_code: None,
+            stdout: vec![],
+            stderr: vec![],
+            errors: vec![],
+            duration: Duration::default(),
+        }
+    }
+
+    pub fn with_output(mut self, output: &Output) -> Self {
+        self.exit_code = output.status.code();
+        self.stdout = output.stdout.clone();
+        self.stderr = output.stderr.clone();
+
+        self
+    }
+
+    pub fn with_stdout(mut self, stdout: Vec<u8>) -> Self {
+        self.stdout = stdout;
+
+        self
+    }
+
+    pub fn with_stderr(mut self, stderr: Vec<u8>) -> Self {
+        self.stderr = stderr;
+
+        self
+    }
+
+    pub fn with_errors(mut self, errors: Vec<String>) -> Self {
+        self.errors = errors;
+
+        self
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+
+        self
+    }
+
+    pub fn truncate(output: &[u8], limit: usize) -> String {
+        if output.len() > limit {
+            format!(
+                "{}... ({} bytes truncated)",
+                String::from_utf8_lossy(&output[..limit]),
+                output.len() - limit
+            )
+        } else {
+            String::from_utf8_lossy(output).to_string()
+        }
+    }
+
+    pub fn headers<S: AsRef<str>>(
+        &self,
+        queue_name: S,
+        delivery: &Delivery,
+        attempts: i64,
+        limit: usize,
+    ) -> FieldTable {
+        let mut headers = delivery.properties.headers().clone().unwrap_or_default();
+        let mut insert = |name: &str, value: AMQPValue| {
+            headers.insert(
+                ShortString::from(format!("{}{}", HEADER_PREFIX, name)),
+                value,
+            )
+        };
+
+        insert("reason", Self::string(&self.reason));
+        if let Some(exit_code) = self.exit_code {
+            insert("exit-code", AMQPValue::LongInt(exit_code));
+        }
+        insert(
+            "stderr",
+            Self::string(Self::truncate(&self.stderr, limit.min(HEADER_OUTPUT_LIMIT))),
+        );
+        insert("command", Self::string(&self.command));
+        if !self.errors.is_empty() {
+            insert(
+                "errors",
+                AMQPValue::FieldArray(FieldArray::from(
+                    self.errors.iter().map(Self::string).collect::<Vec<_>>(),
+                )),
+            );
+        }
+        insert("hostname", Self::string(utils::hostname()));
+        insert("queue", Self::string(queue_name.as_ref()));
+        insert("exchange", Self::string(delivery.exchange.as_str()));
+        insert("routing-key", Self::string(delivery.routing_key.as_str()));
+        insert("attempts", AMQPValue::LongLongInt(attempts));
+        insert(
+            "timestamp",
+            AMQPValue::Timestamp(Utc::now().timestamp() as u64),
+        );
+
+        headers
+    }
+
+    pub fn reply(&self, reply_mode: ReplyMode) -> Vec<u8> {
+        match reply_mode {
+            ReplyMode::Raw => self.stdout.clone(),
+            ReplyMode::Json => json!({
+                "exit_code": self.exit_code,
+                "stdout": String::from_utf8_lossy(&self.stdout),
+                "reason": self.reason,
+                "errors": self.errors,
+            })
+            .to_string()
+            .into_bytes(),
+        }
+    }
+
+    pub fn event<S: AsRef<str>>(
+        &self,
+        queue_name: S,
+        index: i32,
+        delivery: &Delivery,
+        outcome: Outcome,
+        limit: usize,
+    ) -> Vec<u8> {
+        let properties = &delivery.properties;
+
+        json!({
+            "queue": queue_name.as_ref(),
+            "consumer": index,
+            "hostname": utils::hostname(),
+            "message_id": properties.message_id().as_ref().map(|id| id.as_str()),
+            "correlation_id": properties.correlation_id().as_ref().map(|id| id.as_str()),
+            "command": self.command,
+            "exit_code": self.exit_code,
+            "reason": self.reason,
+            "errors": self.errors,
+            "timeout": self.reason == TIMEOUT_REASON,
+            "outcome": outcome.to_string(),
+            "duration_ms": self.duration.as_millis() as u64,
+            "stdout": Self::truncate(&self.stdout, limit),
+            "stderr": Self::truncate(&self.stderr, limit),
+            "timestamp": Utc::now().timestamp(),
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    fn string<S: AsRef<str>>(value: S) -> AMQPValue {
+        AMQPValue::LongString(LongString::from(value.as_ref()))
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::io;
+
+use async_std::sync::{Arc, Mutex, RwLock};
+
+use log::{error, info};
+
+use futures::future::{BoxFuture, FutureExt};
+
+use base64::encode as base64_encode;
+
+use crate::client::consumer::handler::report::{Report, TIMEOUT_REASON};
+use crate::client::consumer::handler::{Handled, HandlerContext, MessageHandler};
+use crate::client::consumer::message::command::CommandError;
+use crate::client::consumer::output::Capture;
+use crate::client::consumer::worker::{Worker, WorkerError};
+use crate::config::queue::outcome::Outcome;
+use crate::config::queue::{Charset, Queue, RetryType};
+
+pub struct WorkerHandler {
+    queue: Arc<RwLock<Queue>>,
+    workers: Mutex<HashMap<(i32, i32), Vec<Worker>>>,
+}
+
+impl WorkerHandler {
+    pub fn new(queue: Arc<RwLock<Queue>>) -> Self {
+        WorkerHandler {
+            queue,
+            workers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn send(&self, context: &HandlerContext<'_>) -> io::Result<Handled> {
+        let index = context.index;
+        let queue_config = context.queue_config;
+        let delivery = context.delivery;
+
+        let cmd = self.queue.write().await.get_command(queue_config.id);
+        let timeout = self
+            .queue
+            .write()
+            .await
+            .get_command_timeout(queue_config.id);
+        let kill_grace = self.queue.write().await.get_kill_grace(queue_config.id);
+        let outcomes = queue_config.outcomes.clone().unwrap_or_default();
+
+        let body = if queue_config.base64 {
+            base64_encode(&delivery.data)
+        } else {
+            let charset = self.queue.write().await.get_charset(queue_config.id);
+            match charset.unwrap_or(Charset::Utf8).decode(&delivery.data) {
+                Some(body) => body,
+                None => {
+                    error!(
+                        "[{}] Message body can't be decoded for the worker \"{}\" on consumer #{}",
+                        queue_config.queue_name, cmd, index
+                    );
+
+                    return Ok(Handled::new(
+                        Outcome::Reject,
+                        Report::new(
+                            format!(
+                                "Message not sent to the worker \"{}\" on consumer #{} with an undecodable body",
+                                cmd, index
+                            ),
+                            cmd.clone(),
+                            CommandError::UndecodableBody.to_string(),
+                        ),
+                    ));
+                }
+            }
+        };
+
+        // Every message processed at the same time by the same consumer gets a worker of
+        // its own: the idle ones are reused, so there are at most as many workers as the
+        // concurrency of the consumer.
+        let idle = self
+            .workers
+            .lock()
+            .await
+            .get_mut(&(queue_config.id, index))
+            .and_then(Vec::pop);
+
+        let mut worker = match idle {
+            Some(worker) if worker.command() == cmd => worker,
+            idle => {
+                // The old worker is stopped before the new one starts.
+                if let Some(worker) = idle {
+                    worker.stop(kill_grace).await;
+                }
+
+                match Worker::spawn(&cmd, queue_config, Capture::for_queue(index, queue_config)) {
+                    Ok(worker) => {
+                        info!(
+                            "[{}] Worker \"{}\" started with pid {} on consumer #{}",
+                            queue_config.queue_name,
+                            cmd,
+                            worker.id().unwrap_or_default(),
+                            index
+                        );
+
+                        worker
+                    }
+                    Err(e) => {
+                        return Ok(Handled::new(
+                            outcomes.error(),
+                            Report::new(
+                                format!(
+                                    "Error ({}) starting the worker \"{}\" on consumer #{}",
+                                    e, cmd, index
+                                ),
+                                cmd.clone(),
+                                e.to_string(),
+                            ),
+                        ));
+                    }
+                }
+            }
+        };
+
+        info!(
+            "[{}] Sending message to the worker \"{}\" on consumer #{}",
+            queue_config.queue_name, cmd, index
+        );
+
+        let (outcome, report) = match worker.send(body, &context.variables(), timeout).await {
+            Ok(response) => {
+                self.workers
+                    .lock()
+                    .await
+                    .entry((queue_config.id, index))
+                    .or_insert_with(Vec::new)
+                    .push(worker);
+
+                (
+                    match self.queue.write().await.get_retry_type(queue_config.id) {
+                        RetryType::Ignored => Outcome::Ack,
+                        _ => response.outcome,
+                    },
+                    Report::new(
+                        format!(
+                            "Worker \"{}\" answered \"{}\" on consumer #{}",
+                            cmd, response.outcome, index
+                        ),
+                        cmd.clone(),
+                        response.outcome.to_string(),
+                    )
+                    .with_stdout(response.output.into_bytes()),
+                )
+            }
+            // The worker is stopped on every error: a new one is started for the next
+            // message.
+            Err(e) => {
+                error!(
+                    "[{}] Worker \"{}\" stopped on consumer #{}: {}",
+                    queue_config.queue_name, cmd, index, e
+                );
+
+                if worker.stop(kill_grace).await {
+                    error!(
+                        "[{}] Worker \"{}\" killed after {} milliseconds on consumer #{}",
+                        queue_config.queue_name, cmd, kill_grace, index
+                    );
+                }
+
+                (
+                    match e {
+                        WorkerError::Crashed(_) => Outcome::Requeue,
+                        WorkerError::InvalidFrame(_) => outcomes.error(),
+                        WorkerError::Timeout => outcomes.timeout(),
+                    },
+                    Report::new(
+                        format!(
+                            "Error ({}) sending the message to the worker \"{}\" on consumer #{}",
+                            e, cmd, index
+                        ),
+                        cmd.clone(),
+                        match e {
+                            WorkerError::Timeout => TIMEOUT_REASON.into(),
+                            e => e.to_string(),
+                        },
+                    ),
+                )
+            }
+        };
+
+        Ok(Handled::new(outcome, report))
+    }
+}
+
+impl MessageHandler for WorkerHandler {
+    fn handle<'a>(&'a self, context: &'a HandlerContext<'a>) -> BoxFuture<'a, io::Result<Handled>> {
+        self.send(context).boxed()
+    }
+}
@@ -0,0 +1,245 @@
+use std::fmt;
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::task;
+
+use tokio_native_tls::TlsConnector;
+
+use url::{Host, Url};
+
+const SCHEME: &str = "http";
+const TLS_SCHEME: &str = "https";
+const HEADERS_END: &[u8] = b"\r\n\r\n";
+
+#[derive(Debug)]
+pub enum HttpError {
+    InvalidUrl(String),
+    InvalidResponse(String),
+    TlsError(native_tls::Error),
+    IoError(io::Error),
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::InvalidUrl(e) => write!(f, "invalid URL: {}", e),
+            HttpError::InvalidResponse(e) => write!(f, "invalid HTTP response: {}", e),
+            HttpError::TlsError(e) => write!(f, "TLS error: {}", e),
+            HttpError::IoError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+type HttpResult<T> = Result<T, HttpError>;
+
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn header<S: AsRef<str>>(&self, name: S) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name.as_ref()))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn parse(response: &[u8]) -> HttpResult<Self> {
+        let invalid = |e: &str| HttpError::InvalidResponse(e.into());
+
+        let end = response
+            .windows(HEADERS_END.len())
+            .position(|window| window == HEADERS_END)
+            .ok_or_else(|| invalid("incomplete headers"))?;
+        let head = String::from_utf8_lossy(&response[..end]);
+        let mut lines = head.split("\r\n");
+
+        let mut status_line = lines
+            .next()
+            .ok_or_else(|| invalid("missing status line"))?
+            .splitn(3, ' ');
+        if !status_line.next().unwrap_or_default().starts_with("HTTP/") {
+            return Err(invalid("missing HTTP version"));
+        }
+        let status = status_line
+            .next()
+            .and_then(|status| status.parse::<u16>().ok())
+            .ok_or_else(|| invalid("invalid status code"))?;
+        let reason = status_line.next().unwrap_or_default().to_string();
+
+        let headers = lines
+            .filter_map(|line| {
+                line.find(':').map(|position| {
+                    (
+                        line[..position].trim().to_string(),
+                        line[position + 1..].trim().to_string(),
+                    )
+                })
+            })
+            .collect::<Vec<(String, String)>>();
+
+        let mut parsed = HttpResponse {
+            status,
+            reason,
+            headers,
+            body: vec![],
+        };
+        parsed.body = parsed.decode_body(&response[end + HEADERS_END.len()..])?;
+
+        Ok(parsed)
+    }
+
+    fn decode_body(&self, body: &[u8]) -> HttpResult<Vec<u8>> {
+        let chunked = self
+            .header("transfer-encoding")
+            .map(|encoding| encoding.eq_ignore_ascii_case("chunked"))
+            .unwrap_or(false);
+        if chunked {
+            return Self::dechunk(body);
+        }
+
+        match self
+            .header("content-length")
+            .and_then(|length| length.parse::<usize>().ok())
+        {
+            Some(length) if length <= body.len() => Ok(body[..length].to_vec()),
+            _ => Ok(body.to_vec()),
+        }
+    }
+
+    fn dechunk(mut body: &[u8]) -> HttpResult<Vec<u8>> {
+        let invalid = || HttpError::InvalidResponse("invalid chunked body".into());
+        let mut decoded = vec![];
+
+        loop {
+            let line = body
+                .windows(2)
+                .position(|window| window == b"\r\n")
+                .ok_or_else(invalid)?;
+            let size = String::from_utf8_lossy(&body[..line]);
+            let size = usize::from_str_radix(size.split(';').next().unwrap_or_default().trim(), 16)
+                .map_err(|_| invalid())?;
+            body = &body[line + 2..];
+
+            if size == 0 {
+                return Ok(decoded);
+            }
+            if body.len() < size {
+                return Err(invalid());
+            }
+
+            decoded.extend_from_slice(&body[..size]);
+            body = body.get(size + 2..).ok_or_else(invalid)?;
+        }
+    }
+}
+
+pub async fn post<S: AsRef<str>>(
+    url: S,
+    headers: &[(String, String)],
+    body: &[u8],
+    limit: usize,
+) -> HttpResult<HttpResponse> {
+    let url = Url::parse(url.as_ref()).map_err(|e| HttpError::InvalidUrl(e.to_string()))?;
+    if url.scheme() != SCHEME && url.scheme() != TLS_SCHEME {
+        return Err(HttpError::InvalidUrl(format!(
+            "unsupported scheme \"{}\"",
+            url.scheme()
+        )));
+    }
+
+    let host = url
+        .host_str()
+        .ok_or_else(|| HttpError::InvalidUrl("missing host".into()))?;
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        path,
+        match url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        },
+        body.len()
+    );
+    for (name, value) in headers {
+        // Header values taken from the message can't break the request.
+        request.push_str(&format!(
+            "{}: {}\r\n",
+            name,
+            value.replace(['\r', '\n'], " ")
+        ));
+    }
+    request.push_str("\r\n");
+
+    // The name resolution is blocking, so it's moved off the runtime threads.
+    let resolved = url.clone();
+    let addresses = task::spawn_blocking(move || resolved.socket_addrs(|| None))
+        .await
+        .map_err(|e| HttpError::IoError(io::Error::other(e)))?
+        .map_err(HttpError::IoError)?;
+    let stream = TcpStream::connect(&addresses[..])
+        .await
+        .map_err(HttpError::IoError)?;
+
+    let response = if url.scheme() == TLS_SCHEME {
+        // The certificate is verified against the bare domain or IP address, without brackets.
+        let domain = match url.host() {
+            Some(Host::Domain(domain)) => domain.to_string(),
+            Some(Host::Ipv4(address)) => address.to_string(),
+            Some(Host::Ipv6(address)) => address.to_string(),
+            None => return Err(HttpError::InvalidUrl("missing host".into())),
+        };
+        let connector =
+            TlsConnector::from(native_tls::TlsConnector::new().map_err(HttpError::TlsError)?);
+        let stream = connector
+            .connect(&domain, stream)
+            .await
+            .map_err(HttpError::TlsError)?;
+
+        exchange(stream, request.as_bytes(), body, limit).await?
+    } else {
+        exchange(stream, request.as_bytes(), body, limit).await?
+    };
+
+    HttpResponse::parse(&response)
+}
+
+async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    request: &[u8],
+    body: &[u8],
+    limit: usize,
+) -> HttpResult<Vec<u8>> {
+    stream
+        .write_all(request)
+        .await
+        .map_err(HttpError::IoError)?;
+    stream.write_all(body).await.map_err(HttpError::IoError)?;
+    stream.flush().await.map_err(HttpError::IoError)?;
+
+    // One byte more than the limit tells a response of exactly `limit` bytes from a larger one.
+    let mut response = vec![];
+    (&mut stream)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut response)
+        .await
+        .map_err(HttpError::IoError)?;
+    if response.len() > limit {
+        return Err(HttpError::InvalidResponse(format!(
+            "response larger than {} bytes",
+            limit
+        )));
+    }
+
+    Ok(response)
+}
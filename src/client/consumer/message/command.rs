@@ -0,0 +1,423 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Write};
+use std::os::unix::fs::fchown;
+use std::process::{Output, Stdio};
+use std::thread;
+use std::time::Instant;
+
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::time::{self, Duration};
+
+use futures::future::join4;
+use futures::pin_mut;
+
+use tempfile::NamedTempFile;
+
+use lapin::message::Delivery;
+
+use crate::client::consumer::message::payload::Payload;
+use crate::client::consumer::message::template::Template;
+use crate::client::consumer::output::{Capture, Stream};
+use crate::config::queue::config::QueueConfig;
+use crate::config::queue::DeliveryMode;
+
+const SHELL: &str = "/bin/sh";
+const GROUP_POLL: Duration = Duration::from_millis(50);
+
+#[derive(Debug)]
+pub enum CommandError {
+    InvalidCommand(String),
+    InvalidBody(String),
+    UndecodableBody,
+    MissingPlaceholder(String),
+    IoError(io::Error),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::InvalidCommand(e) => write!(f, "invalid command: {}", e),
+            CommandError::InvalidBody(e) => write!(f, "invalid message body: {}", e),
+            CommandError::UndecodableBody => write!(f, "undecodable message body"),
+            CommandError::MissingPlaceholder(key) => {
+                write!(f, "missing value for placeholder \"{{{{{}}}}}\"", key)
+            }
+            CommandError::IoError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+pub enum CommandResult {
+    Timeout(bool),
+    Output(io::Result<Output>),
+}
+
+type CommandBuildResult<T> = Result<T, CommandError>;
+
+pub struct MessageCommand {
+    pub command: Command,
+    pub human: String,
+    stdin: Option<Vec<u8>>,
+    file: Option<NamedTempFile>,
+}
+
+impl MessageCommand {
+    pub fn new<S: AsRef<str>>(
+        cmd: S,
+        queue_config: &QueueConfig,
+        delivery_mode: DeliveryMode,
+        payload: &Payload,
+        delivery: &Delivery,
+    ) -> CommandBuildResult<Self> {
+        // The body is needed as text when it's used by placeholders, as arguments or
+        // when it has to be transcoded, otherwise the original bytes are delivered.
+        let needs_text = Template::uses_body(cmd.as_ref())
+            || !queue_config.base64
+                && (matches!(delivery_mode, DeliveryMode::Argv) || payload.transcoded);
+        if needs_text && !payload.is_decoded() {
+            return Err(CommandError::UndecodableBody);
+        }
+
+        let shell = queue_config.shell.unwrap_or(false);
+        let mut template = Template::new(delivery, payload.text.as_deref());
+        let mut arguments = shell_words::split(cmd.as_ref())
+            .map_err(|e| CommandError::InvalidCommand(format!("{}", e)))?;
+        if arguments.is_empty() {
+            return Err(CommandError::InvalidCommand("empty command".into()));
+        }
+
+        let rendered = if shell {
+            template
+                .render(cmd.as_ref(), true)
+                .map_err(CommandError::MissingPlaceholder)?
+        } else {
+            arguments = arguments
+                .iter()
+                .map(|argument| template.render(argument, false))
+                .collect::<Result<Vec<String>, String>>()
+                .map_err(CommandError::MissingPlaceholder)?;
+
+            shell_words::join(&arguments)
+        };
+
+        let mut stdin = None;
+        let mut file = None;
+        let body_arguments = match delivery_mode {
+            DeliveryMode::Argv => {
+                if Template::uses_body(cmd.as_ref()) {
+                    vec![]
+                } else if queue_config.base64 {
+                    vec!["--body".into(), payload.argument(true)]
+                } else {
+                    shell_words::split(&payload.argument(false))
+                        .map_err(|e| CommandError::InvalidBody(format!("{}", e)))?
+                }
+            }
+            DeliveryMode::Stdin => {
+                stdin = Some(payload.data.clone());
+
+                vec![]
+            }
+            DeliveryMode::Tempfile => {
+                let mut temp = NamedTempFile::new().map_err(CommandError::IoError)?;
+                temp.write_all(&payload.data)
+                    .map_err(CommandError::IoError)?;
+
+                // The file is readable by its owner only: a command running as another
+                // user has to own it.
+                if queue_config.uid.is_some() || queue_config.gid.is_some() {
+                    fchown(
+                        temp.as_file(),
+                        queue_config
+                            .uid
+                            .map(|uid| Self::id("uid", uid))
+                            .transpose()?,
+                        queue_config
+                            .gid
+                            .map(|gid| Self::id("gid", gid))
+                            .transpose()?,
+                    )
+                    .map_err(CommandError::IoError)?;
+                }
+
+                let path = temp.path().to_string_lossy().to_string();
+                file = Some(temp);
+
+                vec!["--body-file".into(), path]
+            }
+        };
+
+        let mut human = rendered.clone();
+        if stdin.is_some() {
+            human.push_str(" < (stdin)");
+        } else if !body_arguments.is_empty() {
+            human.push_str(&format!(" {}", shell_words::join(&body_arguments)));
+        }
+
+        let command = if shell {
+            let mut command = Command::new(SHELL);
+            command.arg("-c").arg(if body_arguments.is_empty() {
+                rendered
+            } else {
+                format!("{} {}", rendered, shell_words::join(&body_arguments))
+            });
+
+            command
+        } else {
+            let mut command = Command::new(arguments.remove(0));
+            command.args(arguments).args(body_arguments);
+
+            command
+        };
+        let command = Self::configure(command, queue_config)?;
+
+        Ok(MessageCommand {
+            command,
+            human,
+            stdin,
+            file,
+        })
+    }
+
+    pub fn batch<S: AsRef<str>>(
+        cmd: S,
+        queue_config: &QueueConfig,
+        lines: Vec<u8>,
+    ) -> CommandBuildResult<Self> {
+        // A batch has no single message to take the placeholders from: the bodies are
+        // always sent to the standard input.
+        Ok(MessageCommand {
+            command: Self::plain(cmd.as_ref(), queue_config)?,
+            human: format!("{} < (stdin)", cmd.as_ref()),
+            stdin: Some(lines),
+            file: None,
+        })
+    }
+
+    pub fn plain<S: AsRef<str>>(cmd: S, queue_config: &QueueConfig) -> CommandBuildResult<Command> {
+        let mut arguments = shell_words::split(cmd.as_ref())
+            .map_err(|e| CommandError::InvalidCommand(format!("{}", e)))?;
+        if arguments.is_empty() {
+            return Err(CommandError::InvalidCommand("empty command".into()));
+        }
+
+        let command = if queue_config.shell.unwrap_or(false) {
+            let mut command = Command::new(SHELL);
+            command.arg("-c").arg(cmd.as_ref());
+
+            command
+        } else {
+            let mut command = Command::new(arguments.remove(0));
+            command.args(arguments);
+
+            command
+        };
+
+        Self::configure(command, queue_config)
+    }
+
+    fn configure(mut command: Command, queue_config: &QueueConfig) -> CommandBuildResult<Command> {
+        if let Some(ref working_dir) = queue_config.working_dir {
+            command.current_dir(working_dir);
+        }
+        if queue_config.env_clear.unwrap_or(false) {
+            command.env_clear();
+        }
+        if let Some(ref env) = queue_config.env {
+            command.envs(env.variables().to_vec());
+        }
+        if let Some(gid) = queue_config.gid {
+            command.gid(Self::id("gid", gid)?);
+        }
+        if let Some(uid) = queue_config.uid {
+            command.uid(Self::id("uid", uid)?);
+        }
+
+        let limits = [
+            (libc::RLIMIT_CPU, queue_config.rlimit_cpu),
+            (libc::RLIMIT_AS, queue_config.rlimit_as),
+            (libc::RLIMIT_NOFILE, queue_config.rlimit_nofile),
+        ];
+        if limits.iter().any(|(_, limit)| limit.is_some()) {
+            unsafe {
+                command.pre_exec(move || {
+                    for (resource, limit) in limits.iter() {
+                        if let Some(limit) = limit {
+                            let rlimit = libc::rlimit {
+                                rlim_cur: *limit as libc::rlim_t,
+                                rlim_max: *limit as libc::rlim_t,
+                            };
+                            if libc::setrlimit(*resource, &rlimit) != 0 {
+                                return Err(io::Error::last_os_error());
+                            }
+                        }
+                    }
+
+                    Ok(())
+                });
+            }
+        }
+
+        Ok(command)
+    }
+
+    fn id(name: &str, id: u64) -> CommandBuildResult<u32> {
+        u32::try_from(id)
+            .map_err(|_| CommandError::InvalidCommand(format!("invalid {} {}", name, id)))
+    }
+
+    pub async fn execute(
+        &mut self,
+        timeout: u64,
+        kill_grace: u64,
+        capture: &Capture,
+    ) -> CommandResult {
+        let stdin = self.stdin.take();
+        let spawned = Self::process_group(&mut self.command)
+            .stdin(if stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => return CommandResult::Output(Err(e)),
+        };
+
+        let pid = child.id();
+        let mut guard = GroupGuard { pid, kill_grace };
+        let write = {
+            let child_stdin = child.stdin.take();
+            async move {
+                if let (Some(mut child_stdin), Some(body)) = (child_stdin, stdin) {
+                    // The command may exit without reading the whole body: its exit code
+                    // decides the outcome, so a broken pipe here is not an error.
+                    let _ = child_stdin.write_all(&body).await;
+                }
+            }
+        };
+
+        // The output is streamed to the log while the command runs, only the first bytes
+        // are kept for the report.
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        let output = async move {
+            let (_, stdout, stderr, status) = join4(
+                write,
+                async {
+                    match stdout {
+                        Some(stdout) => capture.drain(stdout, Stream::Stdout).await,
+                        None => vec![],
+                    }
+                },
+                async {
+                    match stderr {
+                        Some(stderr) => capture.drain(stderr, Stream::Stderr).await,
+                        None => vec![],
+                    }
+                },
+                child.wait(),
+            )
+            .await;
+
+            status.map(|status| Output {
+                status,
+                stdout,
+                stderr,
+            })
+        };
+        pin_mut!(output);
+
+        let result = match time::timeout(Duration::from_millis(timeout), &mut output).await {
+            Ok(output) => CommandResult::Output(output),
+            Err(_) => {
+                Self::signal(pid, libc::SIGTERM);
+                if time::timeout(Duration::from_millis(kill_grace), &mut output)
+                    .await
+                    .is_err()
+                {
+                    Self::signal(pid, libc::SIGKILL);
+
+                    let _ = output.await;
+
+                    CommandResult::Timeout(true)
+                } else {
+                    CommandResult::Timeout(false)
+                }
+            }
+        };
+        guard.disarm();
+
+        result
+    }
+
+    pub fn process_group(command: &mut Command) -> &mut Command {
+        unsafe {
+            command.pre_exec(|| {
+                // Run the command in its own process group, so that the whole tree can be
+                // signaled on timeout.
+                if libc::setpgid(0, 0) == 0 {
+                    Ok(())
+                } else {
+                    Err(io::Error::last_os_error())
+                }
+            })
+        }
+    }
+
+    pub fn signal(pid: Option<u32>, signal: libc::c_int) {
+        if let Some(pid) = pid {
+            unsafe {
+                libc::killpg(pid as libc::pid_t, signal);
+            }
+        }
+    }
+
+    pub fn close(&mut self) -> io::Result<()> {
+        match self.file.take() {
+            Some(file) => file.close(),
+            None => Ok(()),
+        }
+    }
+}
+
+// Stops the process group of a command whose execution is dropped before the command
+// exits, e.g. when the consumer shuts down or restarts, so that it isn't left running
+// while its message is redelivered.
+struct GroupGuard {
+    pid: Option<u32>,
+    kill_grace: u64,
+}
+
+impl GroupGuard {
+    fn disarm(&mut self) {
+        self.pid = None;
+    }
+}
+
+impl Drop for GroupGuard {
+    fn drop(&mut self) {
+        if let Some(pid) = self.pid.take() {
+            MessageCommand::signal(Some(pid), libc::SIGTERM);
+
+            // Dropping can't wait, the kill grace period runs on its own thread.
+            let kill_grace = Duration::from_millis(self.kill_grace);
+            thread::spawn(move || {
+                let start = Instant::now();
+                while start.elapsed() < kill_grace
+                    && unsafe { libc::killpg(pid as libc::pid_t, 0) } == 0
+                {
+                    thread::sleep(GROUP_POLL);
+                }
+
+                MessageCommand::signal(Some(pid), libc::SIGKILL);
+            });
+        }
+    }
+}
@@ -0,0 +1,1013 @@
+pub mod command;
+pub mod payload;
+mod publisher;
+pub mod retry;
+pub mod template;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use async_std::sync::{Arc, Mutex, RwLock};
+
+use log::{error, info};
+
+use futures::TryFutureExt;
+
+use tokio::task;
+
+use chrono::Utc;
+
+use lapin::message::Delivery;
+use lapin::options::{BasicAckOptions, BasicRejectOptions};
+use lapin::types::ShortString;
+use lapin::{BasicProperties, Channel, Error as LapinError};
+
+use crate::client::consumer::decompress::{self, DecompressError};
+use crate::client::consumer::dedup::{self, DedupStore};
+use crate::client::consumer::expiry;
+use crate::client::consumer::handler::report::{Report, INVALID_REASON};
+use crate::client::consumer::handler::{
+    CommandHandler, FastCgiHandler, HandlerContext, HttpHandler, MessageHandler, WorkerHandler,
+};
+use crate::client::consumer::message::command::CommandError;
+use crate::client::consumer::schema::{Schema, SchemaError};
+use crate::client::consumer::DEFAULT_WAIT_PART;
+use crate::config::queue::config::QueueConfig;
+use crate::config::queue::outcome::Outcome;
+use crate::config::queue::{retry_queue_name, Charset, HandlerType, Queue, ReplyMode, RetryMode};
+use crate::utils;
+
+#[derive(Debug)]
+pub enum MessageError {
+    LapinError(LapinError),
+    IoError(io::Error),
+}
+
+type MessageResult<T> = Result<T, MessageError>;
+
+const JSON_CONTENT_TYPE: &str = "application/json";
+const DEDUP_HASH: &str = "hash";
+
+pub struct Message {
+    queue: Arc<RwLock<Queue>>,
+    prefix: String,
+    handlers: HashMap<i32, Arc<dyn MessageHandler>>,
+    worker: Arc<WorkerHandler>,
+    dedup: Mutex<HashMap<i32, DedupStore>>,
+    schemas: Mutex<HashMap<i32, Arc<Schema>>>,
+}
+
+impl Message {
+    pub fn new(queue: Arc<RwLock<Queue>>, prefix: String) -> Self {
+        Self {
+            queue: queue.clone(),
+            prefix,
+            handlers: HashMap::new(),
+            worker: Arc::new(WorkerHandler::new(queue)),
+            dedup: Mutex::new(HashMap::new()),
+            schemas: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn add_handler(&mut self, id: i32, handler: Arc<dyn MessageHandler>) {
+        self.handlers.insert(id, handler);
+    }
+
+    pub async fn is_batch(&self, queue_config: &QueueConfig) -> bool {
+        queue_config.has_batch()
+            && !self.handlers.contains_key(&queue_config.id)
+            && matches!(
+                self.queue.write().await.get_handler_type(queue_config.id),
+                HandlerType::Command
+            )
+    }
+
+    pub async fn handle_message(
+        &self,
+        index: i32,
+        queue_config: &QueueConfig,
+        channel: &Channel,
+        delivery: Delivery,
+    ) -> MessageResult<()> {
+        // The handlers get the decompressed body, while the original message is kept for
+        // the retries and the dead letters.
+        let body = match self.admit(index, queue_config, channel, &delivery).await? {
+            Some(body) => body,
+            None => return Ok(()),
+        };
+
+        let handler: Arc<dyn MessageHandler> = match self.handlers.get(&queue_config.id) {
+            Some(handler) => handler.clone(),
+            None => match self.queue.write().await.get_handler_type(queue_config.id) {
+                HandlerType::Command => Arc::new(CommandHandler::new(self.queue.clone())),
+                HandlerType::Http => Arc::new(HttpHandler::new(self.queue.clone())),
+                HandlerType::FastCgi => Arc::new(FastCgiHandler::new(self.queue.clone())),
+                HandlerType::Worker => self.worker.clone(),
+            },
+        };
+
+        let context = HandlerContext {
+            index,
+            queue_name: format!("{}{}", self.prefix, queue_config.queue_name),
+            queue_config,
+            delivery: &body,
+        };
+
+        let started = Instant::now();
+        let (outcome, report) = match handler.handle(&context).await {
+            Ok(handled) => (
+                handled.outcome,
+                handled.report.with_duration(started.elapsed()),
+            ),
+            Err(e) => {
+                error!(
+                    "[{}] Error {:?} handling message on consumer #{}",
+                    queue_config.queue_name, e, index
+                );
+
+                (
+                    queue_config.outcomes.clone().unwrap_or_default().error(),
+                    Report::new(
+                        format!("Message not handled on consumer #{}", index),
+                        String::new(),
+                        e.to_string(),
+                    )
+                    .with_duration(started.elapsed()),
+                )
+            }
+        };
+
+        self.apply_outcome(index, queue_config, channel, &delivery, outcome, report)
+            .await
+    }
+
+    pub async fn handle_batch(
+        &self,
+        index: i32,
+        queue_config: &QueueConfig,
+        channel: &Channel,
+        deliveries: Vec<Delivery>,
+    ) -> MessageResult<()> {
+        // The command gets the decompressed bodies, while the original messages are
+        // settled.
+        let (deliveries, bodies): (Vec<Delivery>, Vec<Delivery>) = self
+            .reject_invalid(index, queue_config, channel, deliveries)
+            .await?
+            .into_iter()
+            .unzip();
+        let delivery_tag = match deliveries.last() {
+            Some(delivery) => delivery.delivery_tag,
+            None => return Ok(()),
+        };
+
+        let started = Instant::now();
+        let handled = CommandHandler::new(self.queue.clone())
+            .execute_batch(index, queue_config, &bodies)
+            .await
+            .map_err(MessageError::IoError)?;
+        let report = handled.report.with_duration(started.elapsed());
+
+        // The channel belongs to this consumer only, so an acknowledged batch is settled
+        // at once up to its last delivery tag.
+        match handled.outcome {
+            Outcome::Ack => {
+                channel
+                    .basic_ack(delivery_tag, BasicAckOptions { multiple: true })
+                    .map_err(MessageError::LapinError)
+                    .await?;
+
+                for delivery in &deliveries {
+                    self.remember(queue_config, delivery).await;
+                }
+
+                info!(
+                    "[{}] {}, {} messages removed.",
+                    queue_config.queue_name,
+                    report.description,
+                    deliveries.len()
+                );
+
+                self.queue.write().await.set_queue_wait(
+                    queue_config.id,
+                    queue_config.retry_wait,
+                    index,
+                    RetryMode::Normal,
+                );
+
+                for delivery in &deliveries {
+                    self.reply(queue_config, channel, delivery, &report).await?;
+                    self.publish_result(
+                        index,
+                        queue_config,
+                        channel,
+                        delivery,
+                        handled.outcome,
+                        &report,
+                    )
+                    .await?;
+                }
+            }
+            // Every other outcome is settled per delivery, so that each message gets
+            // its own retry count, delayed retry tier and dead letter headers.
+            outcome => {
+                let mut wait = false;
+                for delivery in &deliveries {
+                    wait |= self
+                        .settle(index, queue_config, channel, delivery, outcome, &report)
+                        .await?;
+                }
+
+                if wait {
+                    self.retry_wait(index, queue_config).await;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn reject_invalid(
+        &self,
+        index: i32,
+        queue_config: &QueueConfig,
+        channel: &Channel,
+        deliveries: Vec<Delivery>,
+    ) -> MessageResult<Vec<(Delivery, Delivery)>> {
+        let charset = self
+            .queue
+            .write()
+            .await
+            .get_charset(queue_config.id)
+            .unwrap_or(Charset::Utf8);
+
+        let mut valid = vec![];
+        for delivery in deliveries {
+            let body = match self.admit(index, queue_config, channel, &delivery).await? {
+                Some(body) => body.into_owned(),
+                None => continue,
+            };
+
+            // With base64 enabled the original bytes are sent, so every body is valid.
+            if queue_config.base64 || charset.decode(&body.data).is_some() {
+                valid.push((delivery, body));
+
+                continue;
+            }
+
+            let report = Report::new(
+                format!(
+                    "Message body can't be decoded for the batch on consumer #{}",
+                    index
+                ),
+                String::new(),
+                CommandError::UndecodableBody.to_string(),
+            );
+            error!("[{}] {}.", queue_config.queue_name, report.description);
+
+            self.apply_outcome(
+                index,
+                queue_config,
+                channel,
+                &delivery,
+                Outcome::Reject,
+                report,
+            )
+            .await?;
+        }
+
+        Ok(valid)
+    }
+
+    // The checks before a message is handled, the same for single messages and batches:
+    // a message failing one of them is settled here, otherwise its body for the handler is
+    // returned, decompressed when needed.
+    async fn admit<'a>(
+        &self,
+        index: i32,
+        queue_config: &QueueConfig,
+        channel: &Channel,
+        delivery: &'a Delivery,
+    ) -> MessageResult<Option<Cow<'a, Delivery>>> {
+        if self
+            .skip_duplicate(index, queue_config, channel, delivery)
+            .await?
+            || self
+                .skip_stale(index, queue_config, channel, delivery)
+                .await?
+        {
+            return Ok(None);
+        }
+
+        let body = match Self::decompress(queue_config, delivery) {
+            Ok(Some(decompressed)) => Cow::Owned(decompressed),
+            Ok(None) => Cow::Borrowed(delivery),
+            Err(e) => {
+                let report = Report::new(
+                    format!("Message body can't be decompressed on consumer #{}", index),
+                    String::new(),
+                    e.to_string(),
+                );
+                error!(
+                    "[{}] {}: {}.",
+                    queue_config.queue_name, report.description, e
+                );
+
+                self.apply_outcome(
+                    index,
+                    queue_config,
+                    channel,
+                    delivery,
+                    Outcome::Reject,
+                    report,
+                )
+                .await?;
+
+                return Ok(None);
+            }
+        };
+
+        if let Some((outcome, report)) = self.validate(index, queue_config, &body).await {
+            self.apply_outcome(index, queue_config, channel, delivery, outcome, report)
+                .await?;
+
+            return Ok(None);
+        }
+
+        Ok(Some(body))
+    }
+
+    async fn skip_duplicate(
+        &self,
+        index: i32,
+        queue_config: &QueueConfig,
+        channel: &Channel,
+        delivery: &Delivery,
+    ) -> MessageResult<bool> {
+        if !queue_config.has_dedup() {
+            return Ok(false);
+        }
+
+        let key = Self::dedup_key(queue_config, delivery);
+        let is_duplicate = match self.dedup_store(queue_config).await {
+            Some(store) => {
+                let (key, now) = (key.clone(), Utc::now().timestamp());
+                match Self::blocking(move || store.contains(key, now)).await {
+                    Ok(is_duplicate) => is_duplicate,
+                    Err(e) => {
+                        error!(
+                            "[{}] Error {:?} reading the deduplication store.",
+                            queue_config.queue_name, e
+                        );
+
+                        false
+                    }
+                }
+            }
+            None => false,
+        };
+
+        if is_duplicate {
+            channel
+                .basic_ack(delivery.delivery_tag, BasicAckOptions { multiple: false })
+                .map_err(MessageError::LapinError)
+                .await?;
+
+            info!(
+                "[{}] Message \"{}\" already processed on consumer #{}, skipped.",
+                queue_config.queue_name, key, index
+            );
+        }
+
+        Ok(is_duplicate)
+    }
+
+    async fn skip_stale(
+        &self,
+        index: i32,
+        queue_config: &QueueConfig,
+        channel: &Channel,
+        delivery: &Delivery,
+    ) -> MessageResult<bool> {
+        let reason = match expiry::stale(queue_config, delivery, Utc::now().timestamp_millis()) {
+            Some(reason) => reason,
+            None => return Ok(false),
+        };
+
+        let outcome = queue_config.stale_outcome();
+        let report = Report::new(
+            format!("Message expired on consumer #{}", index),
+            String::new(),
+            reason,
+        );
+        info!(
+            "[{}] {}: {} ({}).",
+            queue_config.queue_name, report.description, report.reason, outcome
+        );
+
+        self.apply_outcome(index, queue_config, channel, delivery, outcome, report)
+            .await?;
+
+        Ok(true)
+    }
+
+    async fn remember(&self, queue_config: &QueueConfig, delivery: &Delivery) {
+        if !queue_config.has_dedup() {
+            return;
+        }
+
+        let key = Self::dedup_key(queue_config, delivery);
+        if let Some(store) = self.dedup_store(queue_config).await {
+            let now = Utc::now().timestamp();
+            if let Err(e) = Self::blocking(move || store.insert(key, now)).await {
+                error!(
+                    "[{}] Error {:?} writing the deduplication store.",
+                    queue_config.queue_name, e
+                );
+            }
+        }
+    }
+
+    fn dedup_key(queue_config: &QueueConfig, delivery: &Delivery) -> String {
+        dedup::key(
+            delivery,
+            queue_config.dedup_key.as_deref() == Some(DEDUP_HASH),
+            queue_config.decompress_limit(),
+        )
+    }
+
+    async fn dedup_store(&self, queue_config: &QueueConfig) -> Option<DedupStore> {
+        let mut stores = self.dedup.lock().await;
+        if let Some(store) = stores.get(&queue_config.id) {
+            return Some(store.clone());
+        }
+
+        // By default the store is kept in the working directory, which survives reboots.
+        let path = PathBuf::from(match queue_config.dedup_path {
+            Some(ref path) => path.clone(),
+            None => format!(
+                "rabbitmq-consumer-{}{}.dedup",
+                self.prefix, queue_config.queue_name
+            ),
+        });
+        let (window, now) = (
+            queue_config.dedup_window.unwrap_or_default(),
+            Utc::now().timestamp(),
+        );
+
+        let opened = {
+            let path = path.clone();
+            Self::blocking(move || DedupStore::open(path, window, now)).await
+        };
+        match opened {
+            Ok(store) => {
+                stores.insert(queue_config.id, store.clone());
+
+                Some(store)
+            }
+            Err(e) => {
+                error!(
+                    "[{}] Error {:?} opening the deduplication store \"{}\".",
+                    queue_config.queue_name,
+                    e,
+                    path.display()
+                );
+
+                None
+            }
+        }
+    }
+
+    // Runs the blocking IO of the deduplication store off the runtime threads.
+    async fn blocking<T: Send + 'static, F: FnOnce() -> io::Result<T> + Send + 'static>(
+        f: F,
+    ) -> io::Result<T> {
+        task::spawn_blocking(f)
+            .await
+            .unwrap_or_else(|e| Err(io::Error::other(e)))
+    }
+
+    async fn validate(
+        &self,
+        index: i32,
+        queue_config: &QueueConfig,
+        delivery: &Delivery,
+    ) -> Option<(Outcome, Report)> {
+        let path = queue_config.schema.as_deref()?;
+        let schema = match self.schema(queue_config, path).await {
+            Ok(schema) => schema,
+            Err(e) => {
+                let report = Report::new(
+                    format!("Schema \"{}\" can't be loaded on consumer #{}", path, index),
+                    String::new(),
+                    e.to_string(),
+                );
+                error!(
+                    "[{}] {}: {}.",
+                    queue_config.queue_name, report.description, e
+                );
+
+                return Some((Outcome::Retry, report));
+            }
+        };
+
+        let charset = self
+            .queue
+            .write()
+            .await
+            .get_charset(queue_config.id)
+            .unwrap_or(Charset::Utf8);
+        let errors = match charset.decode(&delivery.data) {
+            Some(text) => schema.validate(text).err()?,
+            None => vec![CommandError::UndecodableBody.to_string()],
+        };
+
+        let report = Report::new(
+            format!(
+                "Message body doesn't match the schema on consumer #{}",
+                index
+            ),
+            String::new(),
+            INVALID_REASON.into(),
+        );
+        error!(
+            "[{}] {}: {}.",
+            queue_config.queue_name,
+            report.description,
+            errors.join("; ")
+        );
+
+        Some((Outcome::DeadLetter, report.with_errors(errors)))
+    }
+
+    async fn schema(
+        &self,
+        queue_config: &QueueConfig,
+        path: &str,
+    ) -> Result<Arc<Schema>, SchemaError> {
+        let mut schemas = self.schemas.lock().await;
+        if let Some(schema) = schemas.get(&queue_config.id) {
+            if schema.path() == Path::new(path) {
+                return Ok(schema.clone());
+            }
+        }
+
+        let schema = Arc::new(Schema::load(path)?);
+        schemas.insert(queue_config.id, schema.clone());
+
+        Ok(schema)
+    }
+
+    fn decompress(
+        queue_config: &QueueConfig,
+        delivery: &Delivery,
+    ) -> Result<Option<Delivery>, DecompressError> {
+        match delivery.properties.content_encoding() {
+            Some(encoding) if decompress::is_compressed(encoding.as_str()) => {
+                let mut decompressed = delivery.clone();
+                decompressed.data = decompress::decompress(
+                    encoding.as_str(),
+                    &delivery.data,
+                    queue_config.decompress_limit(),
+                )?;
+
+                Ok(Some(decompressed))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    async fn apply_outcome(
+        &self,
+        index: i32,
+        queue_config: &QueueConfig,
+        channel: &Channel,
+        delivery: &Delivery,
+        outcome: Outcome,
+        report: Report,
+    ) -> MessageResult<()> {
+        if self
+            .settle(index, queue_config, channel, delivery, outcome, &report)
+            .await?
+        {
+            self.retry_wait(index, queue_config).await;
+        }
+
+        Ok(())
+    }
+
+    // Settles a single delivery, returning whether the consumer has to wait before
+    // handling the next message.
+    async fn settle(
+        &self,
+        index: i32,
+        queue_config: &QueueConfig,
+        channel: &Channel,
+        delivery: &Delivery,
+        outcome: Outcome,
+        report: &Report,
+    ) -> MessageResult<bool> {
+        let retries = retry::retries(delivery);
+        let outcome = match outcome {
+            Outcome::Requeue | Outcome::Retry
+                if retry::exhausted(retries, queue_config.max_retries) =>
+            {
+                info!(
+                    "[{}] Message retried {} times on consumer #{}, maximum retries reached.",
+                    queue_config.queue_name, retries, index
+                );
+
+                Outcome::DeadLetter
+            }
+            _ => outcome,
+        };
+
+        if let Outcome::Ack | Outcome::Reject | Outcome::DeadLetter = outcome {
+            self.reply(queue_config, channel, delivery, report).await?;
+        }
+
+        // Whether the consumer already waited, after failing to republish the message.
+        let mut backed_off = false;
+        match outcome {
+            Outcome::Ack => {
+                channel
+                    .basic_ack(delivery.delivery_tag, BasicAckOptions { multiple: false })
+                    .map_err(MessageError::LapinError)
+                    .await?;
+
+                self.remember(queue_config, delivery).await;
+
+                info!(
+                    "[{}] {}, message removed.",
+                    queue_config.queue_name, report.description
+                );
+
+                self.queue.write().await.set_queue_wait(
+                    queue_config.id,
+                    queue_config.retry_wait,
+                    index,
+                    RetryMode::Normal,
+                );
+            }
+            Outcome::Requeue => {
+                backed_off = self
+                    .requeue(index, queue_config, channel, delivery, retries, report)
+                    .await?;
+            }
+            Outcome::Retry if queue_config.has_delayed_retry() => {
+                backed_off = self
+                    .delay(index, queue_config, channel, delivery, retries, report)
+                    .await?;
+            }
+            Outcome::Retry => {
+                backed_off = self
+                    .requeue(index, queue_config, channel, delivery, retries, report)
+                    .await?;
+            }
+            Outcome::DeadLetter if queue_config.has_dead_letter() => {
+                let published = publisher::publish(
+                    channel,
+                    queue_config
+                        .dead_letter_exchange
+                        .as_deref()
+                        .unwrap_or_default(),
+                    queue_config
+                        .dead_letter_queue
+                        .as_deref()
+                        .unwrap_or_else(|| delivery.routing_key.as_str()),
+                    delivery.data.clone(),
+                    delivery.properties.clone().with_headers(report.headers(
+                        format!("{}{}", self.prefix, queue_config.queue_name),
+                        delivery,
+                        retries + 1,
+                        queue_config.output_limit(),
+                    )),
+                )
+                .await?;
+
+                if published {
+                    channel
+                        .basic_ack(delivery.delivery_tag, BasicAckOptions { multiple: false })
+                        .map_err(MessageError::LapinError)
+                        .await?;
+
+                    info!(
+                        "[{}] {}, message dead-lettered.",
+                        queue_config.queue_name, report.description
+                    );
+                } else {
+                    error!(
+                        "[{}] {}, message not confirmed or not routed by the dead letter exchange, requeued after waiting.",
+                        queue_config.queue_name, report.description
+                    );
+
+                    self.requeue_later(index, queue_config, channel, delivery)
+                        .await?;
+                    backed_off = true;
+                }
+            }
+            Outcome::Reject | Outcome::DeadLetter => {
+                channel
+                    .basic_reject(delivery.delivery_tag, BasicRejectOptions { requeue: false })
+                    .map_err(MessageError::LapinError)
+                    .await?;
+
+                info!(
+                    "[{}] {}, message rejected.",
+                    queue_config.queue_name, report.description
+                );
+            }
+        }
+
+        self.publish_result(index, queue_config, channel, delivery, outcome, report)
+            .await?;
+
+        Ok(matches!(outcome, Outcome::Retry) && !queue_config.has_delayed_retry() && !backed_off)
+    }
+
+    async fn publish_result(
+        &self,
+        index: i32,
+        queue_config: &QueueConfig,
+        channel: &Channel,
+        delivery: &Delivery,
+        outcome: Outcome,
+        report: &Report,
+    ) -> MessageResult<()> {
+        let results_exchange = match queue_config.results_exchange {
+            Some(ref results_exchange) => results_exchange.as_str(),
+            None => return Ok(()),
+        };
+
+        let queue_name = format!("{}{}", self.prefix, queue_config.queue_name);
+        let published = publisher::publish(
+            channel,
+            results_exchange,
+            queue_name.as_str(),
+            report.event(
+                &queue_name,
+                index,
+                delivery,
+                outcome,
+                queue_config.output_limit(),
+            ),
+            BasicProperties::default()
+                .with_content_type(ShortString::from(JSON_CONTENT_TYPE))
+                .with_timestamp(Utc::now().timestamp() as u64),
+        )
+        .await?;
+
+        if !published {
+            error!(
+                "[{}] Result event not confirmed or not routed by the exchange \"{}\".",
+                queue_config.queue_name, results_exchange
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn reply(
+        &self,
+        queue_config: &QueueConfig,
+        channel: &Channel,
+        delivery: &Delivery,
+        report: &Report,
+    ) -> MessageResult<()> {
+        let reply_to = match delivery.properties.reply_to() {
+            Some(reply_to) => reply_to.as_str(),
+            None => return Ok(()),
+        };
+        let reply_mode = match self.queue.write().await.get_reply_mode(queue_config.id) {
+            Some(reply_mode) => reply_mode,
+            None => return Ok(()),
+        };
+
+        let mut properties = BasicProperties::default();
+        if let Some(correlation_id) = delivery.properties.correlation_id() {
+            properties = properties.with_correlation_id(correlation_id.clone());
+        }
+        if let ReplyMode::Json = reply_mode {
+            properties = properties.with_content_type(ShortString::from(JSON_CONTENT_TYPE));
+        }
+
+        if publisher::publish(channel, "", reply_to, report.reply(reply_mode), properties).await? {
+            info!(
+                "[{}] Reply published to \"{}\".",
+                queue_config.queue_name, reply_to
+            );
+        } else {
+            error!(
+                "[{}] Reply not confirmed or not routed to \"{}\".",
+                queue_config.queue_name, reply_to
+            );
+        }
+
+        Ok(())
+    }
+
+    // Requeues the message, returning whether the consumer already waited because the
+    // message couldn't be republished.
+    async fn requeue(
+        &self,
+        index: i32,
+        queue_config: &QueueConfig,
+        channel: &Channel,
+        delivery: &Delivery,
+        retries: i64,
+        report: &Report,
+    ) -> MessageResult<bool> {
+        // Without a retry limit the broker requeue is enough, otherwise the message is
+        // republished to keep track of the retries in its headers.
+        if queue_config.max_retries.is_none() {
+            channel
+                .basic_reject(delivery.delivery_tag, BasicRejectOptions { requeue: true })
+                .map_err(MessageError::LapinError)
+                .await?;
+
+            info!(
+                "[{}] {}, message rejected and requeued.",
+                queue_config.queue_name, report.description
+            );
+
+            return Ok(false);
+        }
+
+        let queue_name = format!("{}{}", self.prefix, queue_config.queue_name);
+        let published = publisher::publish(
+            channel,
+            "",
+            queue_name.as_str(),
+            delivery.data.clone(),
+            delivery
+                .properties
+                .clone()
+                .with_headers(retry::headers(delivery, retries + 1)),
+        )
+        .await?;
+
+        if published {
+            channel
+                .basic_ack(delivery.delivery_tag, BasicAckOptions { multiple: false })
+                .map_err(MessageError::LapinError)
+                .await?;
+
+            info!(
+                "[{}] {}, message republished for retry #{}.",
+                queue_config.queue_name,
+                report.description,
+                retries + 1
+            );
+
+            Ok(false)
+        } else {
+            error!(
+                "[{}] {}, message not confirmed or not routed to \"{}\", requeued after waiting.",
+                queue_config.queue_name, report.description, queue_name
+            );
+
+            self.requeue_later(index, queue_config, channel, delivery)
+                .await?;
+
+            Ok(true)
+        }
+    }
+
+    // Moves the message to its retry queue, returning whether the consumer already waited
+    // because the message couldn't be republished.
+    async fn delay(
+        &self,
+        index: i32,
+        queue_config: &QueueConfig,
+        channel: &Channel,
+        delivery: &Delivery,
+        retries: i64,
+        report: &Report,
+    ) -> MessageResult<bool> {
+        let delays = queue_config.retry_delays();
+        let tier = ((retries + 1) as usize).min(delays.len());
+        let retry_queue = retry_queue_name(
+            format!("{}{}", self.prefix, queue_config.queue_name),
+            delays[tier - 1],
+        );
+
+        let published = publisher::publish(
+            channel,
+            "",
+            retry_queue.as_str(),
+            delivery.data.clone(),
+            delivery
+                .properties
+                .clone()
+                .with_headers(retry::headers(delivery, retries + 1)),
+        )
+        .await?;
+
+        if published {
+            channel
+                .basic_ack(delivery.delivery_tag, BasicAckOptions { multiple: false })
+                .map_err(MessageError::LapinError)
+                .await?;
+
+            info!(
+                "[{}] {}, message delayed {} milliseconds in \"{}\" for retry #{}.",
+                queue_config.queue_name,
+                report.description,
+                delays[tier - 1],
+                retry_queue,
+                retries + 1
+            );
+
+            Ok(false)
+        } else {
+            error!(
+                "[{}] {}, message not confirmed or not routed to the retry queue \"{}\", requeued after waiting.",
+                queue_config.queue_name, report.description, retry_queue
+            );
+
+            self.requeue_later(index, queue_config, channel, delivery)
+                .await?;
+
+            Ok(true)
+        }
+    }
+
+    // A message that can't be republished goes back to the queue only after a wait, so
+    // that a missing or misconfigured exchange doesn't run it again right away.
+    async fn requeue_later(
+        &self,
+        index: i32,
+        queue_config: &QueueConfig,
+        channel: &Channel,
+        delivery: &Delivery,
+    ) -> MessageResult<()> {
+        self.retry_wait(index, queue_config).await;
+
+        channel
+            .basic_reject(delivery.delivery_tag, BasicRejectOptions { requeue: true })
+            .map_err(MessageError::LapinError)
+            .await
+    }
+
+    async fn retry_wait(&self, index: i32, queue_config: &QueueConfig) {
+        let ms = self
+            .queue
+            .write()
+            .await
+            .get_queue_wait(queue_config.id, index);
+
+        info!(
+            "[{}] Waiting {} milliseconds for consumer #{}...",
+            queue_config.queue_name, ms, index
+        );
+
+        self.wait_db(index, queue_config).await;
+
+        self.queue
+            .write()
+            .await
+            .set_queue_wait(queue_config.id, ms, index, RetryMode::Retry);
+    }
+
+    async fn wait_db(&self, index: i32, queue_config: &QueueConfig) {
+        while async {
+            let is_enabled = self.queue.write().await.is_enabled(queue_config.id);
+            if is_enabled {
+                let waiting = self
+                    .queue
+                    .write()
+                    .await
+                    .get_queue_wait(queue_config.id, index) as i64
+                    - DEFAULT_WAIT_PART as i64;
+
+                if waiting <= 0 {
+                    self.queue.write().await.set_queue_wait(
+                        queue_config.id,
+                        queue_config.retry_wait,
+                        index,
+                        RetryMode::Normal,
+                    );
+
+                    None
+                } else {
+                    self.queue.write().await.set_queue_wait(
+                        queue_config.id,
+                        waiting as u64,
+                        index,
+                        RetryMode::Forced,
+                    );
+
+                    utils::wait(DEFAULT_WAIT_PART).await;
+                    Some(())
+                }
+            } else {
+                None
+            }
+        }
+        .await
+        .is_some()
+        {}
+    }
+}
@@ -0,0 +1,44 @@
+use base64::encode as base64_encode;
+
+use crate::config::queue::Charset;
+
+pub struct Payload {
+    pub text: Option<String>,
+    pub data: Vec<u8>,
+    pub transcoded: bool,
+}
+
+impl Payload {
+    pub fn new(data: &[u8], charset: Option<Charset>, base64: bool) -> Self {
+        let transcoded = charset.is_some();
+        let text = charset.unwrap_or(Charset::Utf8).decode(data);
+
+        // The original bytes are kept as they are, unless they have to be transcoded:
+        // the base64 encoding always applies to the original bytes.
+        let data = if base64 {
+            base64_encode(data).into_bytes()
+        } else if transcoded {
+            text.clone().unwrap_or_default().into_bytes()
+        } else {
+            data.to_vec()
+        };
+
+        Payload {
+            text,
+            data,
+            transcoded,
+        }
+    }
+
+    pub fn is_decoded(&self) -> bool {
+        self.text.is_some()
+    }
+
+    pub fn argument(&self, base64: bool) -> String {
+        if base64 {
+            String::from_utf8_lossy(&self.data).to_string()
+        } else {
+            self.text.clone().unwrap_or_default()
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use futures::TryFutureExt;
+
+use log::warn;
+
+use lapin::options::BasicPublishOptions;
+use lapin::publisher_confirm::Confirmation;
+use lapin::{BasicProperties, Channel};
+
+use crate::client::consumer::message::{MessageError, MessageResult};
+
+// The messages are published as mandatory: a message the broker can't route to any queue
+// is returned, and so it's not published.
+pub async fn publish<S: AsRef<str>>(
+    channel: &Channel,
+    exchange: S,
+    routing_key: S,
+    payload: Vec<u8>,
+    properties: BasicProperties,
+) -> MessageResult<bool> {
+    let confirmation = channel
+        .basic_publish(
+            exchange.as_ref(),
+            routing_key.as_ref(),
+            BasicPublishOptions {
+                mandatory: true,
+                ..Default::default()
+            },
+            payload,
+            properties,
+        )
+        .map_err(MessageError::LapinError)
+        .await?
+        .map_err(MessageError::LapinError)
+        .await?;
+
+    match confirmation {
+        Confirmation::Ack(Some(returned)) | Confirmation::Nack(Some(returned)) => {
+            warn!(
+                "Message to \"{}\" with routing key \"{}\" returned: {} {}",
+                exchange.as_ref(),
+                routing_key.as_ref(),
+                returned.reply_code,
+                returned.reply_text
+            );
+
+            Ok(false)
+        }
+        Confirmation::Nack(None) => Ok(false),
+        Confirmation::Ack(None) | Confirmation::NotRequested => Ok(true),
+    }
+}
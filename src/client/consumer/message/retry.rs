@@ -0,0 +1,66 @@
+use lapin::message::Delivery;
+use lapin::types::{AMQPValue, FieldTable, ShortString};
+
+use crate::client::consumer::metadata::Metadata;
+
+pub const RETRY_COUNT_HEADER: &str = "x-retry-count";
+
+const DELIVERY_COUNT_HEADER: &str = "x-delivery-count";
+const DEATH_HEADER: &str = "x-death";
+const DEATH_COUNT: &str = "count";
+
+pub fn retries(delivery: &Delivery) -> i64 {
+    let headers = match delivery.properties.headers() {
+        Some(headers) => headers.inner(),
+        None => return 0,
+    };
+
+    let count = |name: &str| {
+        headers
+            .get(name)
+            .and_then(Metadata::value_to_string)
+            .and_then(|count| count.parse::<i64>().ok())
+            .unwrap_or(0)
+    };
+
+    // The same message may loop through more queues (e.g. a retry queue and back), each
+    // of them tracked by an "x-death" entry: the highest count is the number of retries.
+    let deaths = match headers.get(DEATH_HEADER) {
+        Some(AMQPValue::FieldArray(deaths)) => deaths
+            .as_slice()
+            .iter()
+            .filter_map(|death| match death {
+                AMQPValue::FieldTable(death) => death
+                    .inner()
+                    .get(DEATH_COUNT)
+                    .and_then(Metadata::value_to_string)
+                    .and_then(|count| count.parse::<i64>().ok()),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0),
+        _ => 0,
+    };
+
+    count(RETRY_COUNT_HEADER)
+        .max(count(DELIVERY_COUNT_HEADER))
+        .max(deaths)
+}
+
+// A message already retried max_retries times is not retried again.
+pub fn exhausted(retries: i64, max_retries: Option<i32>) -> bool {
+    match max_retries {
+        Some(max_retries) => retries >= max_retries as i64,
+        None => false,
+    }
+}
+
+pub fn headers(delivery: &Delivery, retries: i64) -> FieldTable {
+    let mut headers = delivery.properties.headers().clone().unwrap_or_default();
+    headers.insert(
+        ShortString::from(RETRY_COUNT_HEADER),
+        AMQPValue::LongLongInt(retries),
+    );
+
+    headers
+}
@@ -0,0 +1,132 @@
+use serde_json::Value;
+
+use lapin::message::Delivery;
+
+use crate::client::consumer::metadata::Metadata;
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+const BODY: &str = "body";
+const BODY_PREFIX: &str = "body.";
+const HEADERS_PREFIX: &str = "headers.";
+
+pub struct Template<'a> {
+    delivery: &'a Delivery,
+    text: Option<&'a str>,
+    body: Option<Option<Value>>,
+}
+
+impl<'a> Template<'a> {
+    pub fn new(delivery: &'a Delivery, text: Option<&'a str>) -> Self {
+        Template {
+            delivery,
+            text,
+            body: None,
+        }
+    }
+
+    pub fn uses_body<S: AsRef<str>>(input: S) -> bool {
+        Self::placeholders(input.as_ref())
+            .iter()
+            .any(|key| *key == BODY || key.starts_with(BODY_PREFIX))
+    }
+
+    pub fn render<S: AsRef<str>>(&mut self, input: S, escape: bool) -> Result<String, String> {
+        let mut input = input.as_ref();
+        let mut output = String::with_capacity(input.len());
+
+        while let Some(start) = input.find(OPEN) {
+            let end = match input[start + OPEN.len()..].find(CLOSE) {
+                Some(end) => start + OPEN.len() + end,
+                None => break,
+            };
+
+            let key = input[start + OPEN.len()..end].trim();
+            let value = self.value(key).ok_or_else(|| key.to_string())?;
+
+            output.push_str(&input[..start]);
+            output.push_str(&if escape {
+                shell_words::quote(&value).to_string()
+            } else {
+                value
+            });
+
+            input = &input[end + CLOSE.len()..];
+        }
+
+        output.push_str(input);
+
+        Ok(output)
+    }
+
+    fn placeholders(mut input: &str) -> Vec<&str> {
+        let mut keys = vec![];
+        while let Some(start) = input.find(OPEN) {
+            match input[start + OPEN.len()..].find(CLOSE) {
+                Some(end) => {
+                    keys.push(input[start + OPEN.len()..start + OPEN.len() + end].trim());
+                    input = &input[start + OPEN.len() + end + CLOSE.len()..];
+                }
+                None => break,
+            }
+        }
+
+        keys
+    }
+
+    fn value(&mut self, key: &str) -> Option<String> {
+        let properties = &self.delivery.properties;
+
+        match key {
+            BODY => self.text.map(String::from),
+            "routing_key" => Some(self.delivery.routing_key.to_string()),
+            "exchange" => Some(self.delivery.exchange.to_string()),
+            "delivery_tag" => Some(self.delivery.delivery_tag.to_string()),
+            "redelivered" => Some(self.delivery.redelivered.to_string()),
+            "message_id" => properties.message_id().as_ref().map(|v| v.to_string()),
+            "correlation_id" => properties.correlation_id().as_ref().map(|v| v.to_string()),
+            "reply_to" => properties.reply_to().as_ref().map(|v| v.to_string()),
+            "content_type" => properties.content_type().as_ref().map(|v| v.to_string()),
+            "timestamp" => properties.timestamp().as_ref().map(|v| v.to_string()),
+            _ => {
+                if let Some(path) = key.strip_prefix(BODY_PREFIX) {
+                    self.body_value(path)
+                } else if let Some(name) = key.strip_prefix(HEADERS_PREFIX) {
+                    properties.headers().as_ref().and_then(|headers| {
+                        headers
+                            .inner()
+                            .iter()
+                            .find(|(header, _)| header.as_str().eq_ignore_ascii_case(name))
+                            .and_then(|(_, value)| Metadata::value_to_string(value))
+                    })
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    fn body_value(&mut self, path: &str) -> Option<String> {
+        let text = self.text;
+        let body = self
+            .body
+            .get_or_insert_with(|| text.and_then(|text| serde_json::from_str(text).ok()))
+            .as_ref()?;
+
+        let value = path
+            .split('.')
+            .try_fold(body, |value, segment| match value {
+                Value::Array(values) => segment
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| values.get(index)),
+                _ => value.get(segment),
+            })?;
+
+        match value {
+            Value::Null => None,
+            Value::String(value) => Some(value.clone()),
+            value => Some(value.to_string()),
+        }
+    }
+}
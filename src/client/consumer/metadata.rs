@@ -0,0 +1,106 @@
+use lapin::message::Delivery;
+use lapin::types::AMQPValue;
+
+const PREFIX: &str = "AMQP_";
+const HEADER_PREFIX: &str = "AMQP_HEADER_";
+const ALL_HEADERS: &str = "*";
+
+pub struct Metadata {
+    variables: Vec<(String, String)>,
+}
+
+impl Metadata {
+    pub fn new<S: AsRef<str>>(
+        queue_name: S,
+        index: i32,
+        delivery: &Delivery,
+        allowed_headers: Option<&str>,
+    ) -> Self {
+        let properties = &delivery.properties;
+        let mut variables = vec![
+            ("QUEUE", queue_name.as_ref().to_string()),
+            ("CONSUMER_INDEX", index.to_string()),
+            ("DELIVERY_TAG", delivery.delivery_tag.to_string()),
+            ("REDELIVERED", delivery.redelivered.to_string()),
+            ("EXCHANGE", delivery.exchange.to_string()),
+            ("ROUTING_KEY", delivery.routing_key.to_string()),
+        ];
+
+        if let Some(message_id) = properties.message_id() {
+            variables.push(("MESSAGE_ID", message_id.to_string()));
+        }
+
+        if let Some(correlation_id) = properties.correlation_id() {
+            variables.push(("CORRELATION_ID", correlation_id.to_string()));
+        }
+
+        if let Some(timestamp) = properties.timestamp() {
+            variables.push(("TIMESTAMP", timestamp.to_string()));
+        }
+
+        let mut variables = variables
+            .into_iter()
+            .map(|(name, value)| (format!("{}{}", PREFIX, name), value))
+            .collect::<Vec<(String, String)>>();
+
+        if let (Some(allowed_headers), Some(headers)) = (allowed_headers, properties.headers()) {
+            let allowed_headers = allowed_headers
+                .split(',')
+                .map(|header| header.trim().to_lowercase())
+                .filter(|header| !header.is_empty())
+                .collect::<Vec<String>>();
+
+            for (name, value) in headers.inner() {
+                let allowed = allowed_headers
+                    .iter()
+                    .any(|header| header == ALL_HEADERS || *header == name.as_str().to_lowercase());
+
+                if let (true, Some(value)) = (allowed, Self::value_to_string(value)) {
+                    variables.push((
+                        format!("{}{}", HEADER_PREFIX, Self::variable_name(name.as_str())),
+                        value,
+                    ));
+                }
+            }
+        }
+
+        Metadata { variables }
+    }
+
+    pub fn variables(&self) -> &[(String, String)] {
+        &self.variables
+    }
+
+    pub fn variable_name<S: AsRef<str>>(name: S) -> String {
+        name.as_ref()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    }
+
+    pub fn value_to_string(value: &AMQPValue) -> Option<String> {
+        match value {
+            AMQPValue::Boolean(v) => Some(v.to_string()),
+            AMQPValue::ShortShortInt(v) => Some(v.to_string()),
+            AMQPValue::ShortShortUInt(v) => Some(v.to_string()),
+            AMQPValue::ShortInt(v) => Some(v.to_string()),
+            AMQPValue::ShortUInt(v) => Some(v.to_string()),
+            AMQPValue::LongInt(v) => Some(v.to_string()),
+            AMQPValue::LongUInt(v) => Some(v.to_string()),
+            AMQPValue::LongLongInt(v) => Some(v.to_string()),
+            AMQPValue::Float(v) => Some(v.to_string()),
+            AMQPValue::Double(v) => Some(v.to_string()),
+            AMQPValue::ShortString(v) => Some(v.to_string()),
+            AMQPValue::LongString(v) => Some(v.to_string()),
+            AMQPValue::Timestamp(v) => Some(v.to_string()),
+            AMQPValue::ByteArray(v) => String::from_utf8(v.as_slice().to_vec()).ok(),
+            _ => None,
+        }
+    }
+}
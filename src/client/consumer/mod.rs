@@ -1,22 +1,38 @@
 pub mod channel;
 pub mod connection;
-mod message;
+pub mod decompress;
+pub mod dedup;
+pub mod expiry;
+pub mod fastcgi;
+pub mod handler;
+pub mod http;
+pub mod message;
+mod metadata;
+pub mod output;
+pub mod schema;
+pub mod worker;
 
 use async_std::sync::{Arc, RwLock};
 
 use log::{error, info};
 
+use std::mem;
+
 use tokio::signal::unix::{signal, SignalKind};
+use tokio::time::{self, Duration, Instant};
 use tokio_stream::StreamExt;
 
-use futures::future::select_all;
 use futures::future::FutureExt;
+use futures::future::{select_all, BoxFuture};
+use futures::stream::FuturesUnordered;
 
+use lapin::message::Delivery;
 use lapin::options::{BasicCancelOptions, BasicConsumeOptions, BasicRecoverOptions};
 use lapin::{types::FieldTable, Channel as LapinChannel, Error as LapinError, Queue as LapinQueue};
 
 use crate::client::consumer::channel::Channel;
 use crate::client::consumer::connection::{Connection, ConnectionError};
+use crate::client::consumer::handler::MessageHandler;
 use crate::client::consumer::message::{Message, MessageError};
 use crate::client::executor::events::{Events, EventsHandler};
 use crate::config::database::Database;
@@ -68,12 +84,18 @@ impl Consumer {
         Self {
             queue: queue.clone(),
             connection: Connection::new(config.rabbit.clone()),
-            message: Message::new(queue),
+            message: Message::new(queue, config.rabbit.queue_prefix.clone()),
             hooks: Vec::new(),
             config,
         }
     }
 
+    pub fn add_handler<H: MessageHandler + 'static>(mut self, id: i32, handler: H) -> Self {
+        self.message.add_handler(id, Arc::new(handler));
+
+        self
+    }
+
     pub async fn run(&mut self) -> ConsumerResult<ConsumerStatus> {
         match self.connection.get_connection().await {
             Ok(connection) => {
@@ -93,6 +115,10 @@ impl Consumer {
 
                 let mut futures = vec![sigint.boxed(), sigquit.boxed(), sigterm.boxed()];
 
+                Channel::declare_exchanges(connection.clone(), &self.config.rabbit.exchanges)
+                    .await
+                    .map_err(ConsumerError::LapinError)?;
+
                 info!("Managing queues...");
 
                 let queues = self.queue.write().await.get_queues();
@@ -107,6 +133,7 @@ impl Consumer {
                                 connection.clone(),
                                 queue.clone(),
                                 self.config.rabbit.queue_prefix.clone(),
+                                &self.config.rabbit.exchanges,
                             )
                             .await
                             {
@@ -165,7 +192,55 @@ impl Consumer {
                     queue_config.queue_name, index, consumer_name
                 );
 
-                while let Some(delivery) = consumer.next().await {
+                let is_batch = self.message.is_batch(&queue_config).await;
+                let mut batch = vec![];
+                let mut deadline = Instant::now();
+                let concurrency = queue_config.concurrency_limit();
+                let mut running: FuturesUnordered<BoxFuture<Result<(), MessageError>>> =
+                    FuturesUnordered::new();
+
+                loop {
+                    let delivery = if running.len() >= concurrency {
+                        if let Some(result) = running.next().await {
+                            result.map_err(ConsumerError::MessageError)?;
+                        }
+
+                        continue;
+                    } else if !running.is_empty() {
+                        tokio::select! {
+                            delivery = consumer.next() => delivery,
+                            Some(result) = running.next() => {
+                                result.map_err(ConsumerError::MessageError)?;
+
+                                continue;
+                            }
+                        }
+                    } else if batch.is_empty() {
+                        consumer.next().await
+                    } else {
+                        match time::timeout_at(deadline, consumer.next()).await {
+                            Ok(delivery) => delivery,
+                            Err(_) => {
+                                self.message
+                                    .handle_batch(
+                                        index,
+                                        &queue_config,
+                                        &channel,
+                                        mem::take(&mut batch),
+                                    )
+                                    .await
+                                    .map_err(ConsumerError::MessageError)?;
+
+                                continue;
+                            }
+                        }
+                    };
+
+                    let delivery = match delivery {
+                        Some(delivery) => delivery,
+                        None => break,
+                    };
+
                     match delivery {
                         Ok((channel, delivery)) => {
                             let is_changed = self
@@ -175,13 +250,55 @@ impl Consumer {
                                 .is_changed(queue_config.id, queue_config.count);
                             let is_enabled = self.queue.write().await.is_enabled(queue_config.id);
 
-                            if !is_changed && is_enabled {
+                            if !is_changed && is_enabled && is_batch {
+                                if batch.is_empty() {
+                                    deadline = Instant::now()
+                                        + Duration::from_millis(queue_config.batch_wait());
+                                }
+                                batch.push(delivery);
+
+                                if batch.len() >= queue_config.batch_limit() {
+                                    self.message
+                                        .handle_batch(
+                                            index,
+                                            &queue_config,
+                                            &channel,
+                                            mem::take(&mut batch),
+                                        )
+                                        .await
+                                        .map_err(ConsumerError::MessageError)?;
+                                }
+                            } else if !is_changed && is_enabled && concurrency > 1 {
+                                // Each delivery is settled on its own tag, so the messages
+                                // can be processed in any order.
+                                let message = &self.message;
+                                let queue_config = &queue_config;
+                                let channel = channel.clone();
+
+                                running.push(
+                                    async move {
+                                        message
+                                            .handle_message(index, queue_config, &channel, delivery)
+                                            .await
+                                    }
+                                    .boxed(),
+                                );
+                            } else if !is_changed && is_enabled {
                                 self.message
                                     .handle_message(index, &queue_config, &channel, delivery)
                                     .await
                                     .map_err(ConsumerError::MessageError)?;
                             }
 
+                            if !is_enabled || is_changed {
+                                while let Some(result) = running.next().await {
+                                    result.map_err(ConsumerError::MessageError)?;
+                                }
+
+                                self.flush(index, &queue_config, &channel, &mut batch)
+                                    .await?;
+                            }
+
                             if !is_enabled {
                                 if !is_changed {
                                     if channel
@@ -239,6 +356,13 @@ impl Consumer {
                     }
                 }
 
+                while let Some(result) = running.next().await {
+                    result.map_err(ConsumerError::MessageError)?;
+                }
+
+                self.flush(index, &queue_config, &channel, &mut batch)
+                    .await?;
+
                 info!("Messages have been processed.");
 
                 Ok(ConsumerStatus::GenericOk)
@@ -261,6 +385,24 @@ impl Consumer {
         .is_some()
         {}
     }
+
+    // Handles the deliveries still waiting in a batch before the consumer stops.
+    async fn flush(
+        &self,
+        index: i32,
+        queue_config: &QueueConfig,
+        channel: &LapinChannel,
+        batch: &mut Vec<Delivery>,
+    ) -> ConsumerResult<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        self.message
+            .handle_batch(index, queue_config, channel, mem::take(batch))
+            .await
+            .map_err(ConsumerError::MessageError)
+    }
 }
 
 impl EventsHandler for Consumer {
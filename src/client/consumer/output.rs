@@ -0,0 +1,185 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+
+use log::{error, info, warn};
+
+use chrono::{SecondsFormat, Utc};
+
+use crate::config::queue::config::QueueConfig;
+
+// Longer lines are split, so that a command writing without newlines can't exhaust the memory.
+const MAX_LINE_LENGTH: u64 = 8192;
+const ROTATED_FILES: usize = 5;
+
+// Serializes the rotations of the logs shared by the consumers of the same queue.
+static ROTATION: Mutex<()> = Mutex::new(());
+
+#[derive(Clone, Copy)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    fn name(self) -> &'static str {
+        match self {
+            Stream::Stdout => "stdout",
+            Stream::Stderr => "stderr",
+        }
+    }
+}
+
+pub struct OutputLog {
+    file: File,
+}
+
+impl OutputLog {
+    pub fn open<P: AsRef<Path>>(path: P, max_size: u64) -> io::Result<Self> {
+        let path = path.as_ref();
+        {
+            let _rotation = ROTATION.lock().unwrap_or_else(|e| e.into_inner());
+            match fs::metadata(path) {
+                Ok(metadata) if metadata.len() >= max_size => Self::rotate(path)?,
+                _ => (),
+            }
+        }
+
+        Ok(OutputLog {
+            file: OpenOptions::new().create(true).append(true).open(path)?,
+        })
+    }
+
+    fn rotate(path: &Path) -> io::Result<()> {
+        let rotated = |generation: usize| {
+            let mut rotated = path.as_os_str().to_owned();
+            rotated.push(format!(".{}", generation));
+
+            PathBuf::from(rotated)
+        };
+
+        for generation in (1..ROTATED_FILES).rev() {
+            match fs::rename(rotated(generation), rotated(generation + 1)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => (),
+            }
+        }
+
+        fs::rename(path, rotated(1))
+    }
+
+    pub fn write<S: AsRef<str>>(&self, prefix: S, kind: &str, line: &[u8]) -> io::Result<()> {
+        let mut record = format!(
+            "{} [{}] {}: ",
+            Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            prefix.as_ref(),
+            kind
+        )
+        .into_bytes();
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        record.extend_from_slice(line.strip_suffix(b"\r").unwrap_or(line));
+        record.push(b'\n');
+
+        // A single write per record, so that the lines of concurrent commands don't mix.
+        (&self.file).write_all(&record)
+    }
+}
+
+pub struct Capture {
+    prefix: String,
+    limit: usize,
+    stdout_limit: usize,
+    log: Option<OutputLog>,
+}
+
+impl Capture {
+    pub fn new<S: Into<String>>(prefix: S, limit: usize, log: Option<OutputLog>) -> Self {
+        Capture {
+            prefix: prefix.into(),
+            limit,
+            stdout_limit: limit,
+            log,
+        }
+    }
+
+    // The whole standard output is kept, e.g. when it's published as a reply.
+    pub fn keep_stdout(mut self) -> Self {
+        self.stdout_limit = usize::MAX;
+
+        self
+    }
+
+    pub fn for_queue(index: i32, queue_config: &QueueConfig) -> Self {
+        let log = match queue_config.output_log {
+            Some(ref path) => match OutputLog::open(path, queue_config.output_log_size()) {
+                Ok(log) => Some(log),
+                Err(e) => {
+                    error!(
+                        "[{}] Error {:?} opening the output log \"{}\" on consumer #{}",
+                        queue_config.queue_name, e, path, index
+                    );
+
+                    None
+                }
+            },
+            None => None,
+        };
+
+        Capture::new(
+            format!("{}#{}", queue_config.queue_name, index),
+            queue_config.output_limit(),
+            log,
+        )
+    }
+
+    pub fn record<S: AsRef<str>>(&self, kind: &str, message: S) {
+        if let Some(ref log) = self.log {
+            if let Err(e) = log.write(&self.prefix, kind, message.as_ref().as_bytes()) {
+                warn!("[{}] Error {:?} writing the output log.", self.prefix, e);
+            }
+        }
+    }
+
+    pub async fn drain<R: AsyncRead + Unpin>(&self, reader: R, stream: Stream) -> Vec<u8> {
+        let mut reader = BufReader::new(reader);
+        let mut retained = vec![];
+        let mut line = vec![];
+
+        loop {
+            line.clear();
+            match (&mut reader)
+                .take(MAX_LINE_LENGTH)
+                .read_until(b'\n', &mut line)
+                .await
+            {
+                Ok(0) | Err(_) => break,
+                Ok(_) => (),
+            }
+
+            let text = String::from_utf8_lossy(&line);
+            let text = text.trim_end_matches(&['\r', '\n'][..]);
+            match stream {
+                Stream::Stdout => info!("[{}] {}", self.prefix, text),
+                Stream::Stderr => warn!("[{}] {}", self.prefix, text),
+            }
+
+            if let Some(ref log) = self.log {
+                if let Err(e) = log.write(&self.prefix, stream.name(), &line) {
+                    warn!("[{}] Error {:?} writing the output log.", self.prefix, e);
+                }
+            }
+
+            let limit = match stream {
+                Stream::Stdout => self.stdout_limit,
+                Stream::Stderr => self.limit,
+            };
+            let available = limit.saturating_sub(retained.len());
+            retained.extend_from_slice(&line[..line.len().min(available)]);
+        }
+
+        retained
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use jsonschema::Validator;
+
+#[derive(Debug)]
+pub enum SchemaError {
+    IoError(io::Error),
+    InvalidJson(serde_json::Error),
+    InvalidSchema(String),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::IoError(e) => write!(f, "{}", e),
+            SchemaError::InvalidJson(e) => write!(f, "invalid JSON: {}", e),
+            SchemaError::InvalidSchema(e) => write!(f, "invalid schema: {}", e),
+        }
+    }
+}
+
+pub struct Schema {
+    path: PathBuf,
+    validator: Validator,
+}
+
+impl Schema {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, SchemaError> {
+        let document = fs::read(path.as_ref()).map_err(SchemaError::IoError)?;
+        let schema = serde_json::from_slice(&document).map_err(SchemaError::InvalidJson)?;
+
+        Self::new(path, &schema)
+    }
+
+    pub fn new<P: AsRef<Path>>(path: P, schema: &Value) -> Result<Self, SchemaError> {
+        Ok(Schema {
+            path: path.as_ref().to_path_buf(),
+            validator: jsonschema::validator_for(schema)
+                .map_err(|e| SchemaError::InvalidSchema(e.to_string()))?,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn validate<S: AsRef<str>>(&self, body: S) -> Result<(), Vec<String>> {
+        let instance: Value = serde_json::from_str(body.as_ref())
+            .map_err(|e| vec![format!("invalid JSON: {}", e)])?;
+
+        let errors = self
+            .validator
+            .iter_errors(&instance)
+            .map(|error| {
+                let path = error.instance_path().to_string();
+
+                format!("{}: {}", if path.is_empty() { "/" } else { &path }, error)
+            })
+            .collect::<Vec<String>>();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::fmt;
+use std::io;
+use std::process::Stdio;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout};
+use tokio::time::{self, Duration};
+
+use serde_json::{json, Map, Value};
+
+use crate::client::consumer::message::command::{CommandError, MessageCommand};
+use crate::client::consumer::output::{Capture, Stream};
+use crate::config::queue::config::QueueConfig;
+use crate::config::queue::outcome::Outcome;
+
+#[derive(Debug)]
+pub enum WorkerError {
+    Crashed(String),
+    InvalidFrame(String),
+    Timeout,
+}
+
+impl fmt::Display for WorkerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorkerError::Crashed(e) => write!(f, "worker crashed: {}", e),
+            WorkerError::InvalidFrame(e) => write!(f, "invalid response frame: {}", e),
+            WorkerError::Timeout => write!(f, "timeout"),
+        }
+    }
+}
+
+pub struct Response {
+    pub outcome: Outcome,
+    pub output: String,
+}
+
+pub struct Worker {
+    command: String,
+    child: Child,
+    pid: Option<u32>,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    sequence: u64,
+}
+
+impl Worker {
+    pub fn spawn<S: AsRef<str>>(
+        cmd: S,
+        queue_config: &QueueConfig,
+        capture: Capture,
+    ) -> Result<Self, CommandError> {
+        let mut command = MessageCommand::plain(cmd.as_ref(), queue_config)?;
+        let mut child = MessageCommand::process_group(&mut command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(CommandError::IoError)?;
+
+        // The standard output carries the answers, the standard error is streamed to the
+        // log for the whole life of the worker.
+        if let Some(stderr) = child.stderr.take() {
+            capture.record("worker", cmd.as_ref());
+            tokio::spawn(async move {
+                capture.drain(stderr, Stream::Stderr).await;
+            });
+        }
+
+        match (child.stdin.take(), child.stdout.take()) {
+            (Some(stdin), Some(stdout)) => Ok(Worker {
+                command: cmd.as_ref().to_string(),
+                pid: child.id(),
+                child,
+                stdin,
+                stdout: BufReader::new(stdout).lines(),
+                sequence: 0,
+            }),
+            _ => Err(CommandError::IoError(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "worker pipes not available",
+            ))),
+        }
+    }
+
+    // Terminates the whole process group of the worker, killing it if it doesn't exit
+    // within the grace period, and waits for it: returns whether it had to be killed.
+    pub async fn stop(mut self, kill_grace: u64) -> bool {
+        MessageCommand::signal(self.pid, libc::SIGTERM);
+        if time::timeout(Duration::from_millis(kill_grace), self.child.wait())
+            .await
+            .is_err()
+        {
+            MessageCommand::signal(self.pid, libc::SIGKILL);
+            let _ = self.child.wait().await;
+
+            return true;
+        }
+
+        false
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn id(&self) -> Option<u32> {
+        self.child.id()
+    }
+
+    pub async fn send<S: AsRef<str>>(
+        &mut self,
+        body: S,
+        variables: &[(String, String)],
+        timeout: u64,
+    ) -> Result<Response, WorkerError> {
+        self.sequence += 1;
+
+        let id = self.sequence;
+        let mut frame = json!({
+            "id": id,
+            "body": body.as_ref(),
+            "variables": variables
+                .iter()
+                .map(|(name, value)| (name.clone(), Value::String(value.clone())))
+                .collect::<Map<String, Value>>(),
+        })
+        .to_string();
+        frame.push('\n');
+
+        let stdin = &mut self.stdin;
+        let stdout = &mut self.stdout;
+        let exchange = async move {
+            stdin.write_all(frame.as_bytes()).await?;
+            stdin.flush().await?;
+
+            stdout.next_line().await
+        };
+
+        match time::timeout(Duration::from_millis(timeout), exchange).await {
+            Ok(Ok(Some(line))) => Self::response(&line, id),
+            Ok(Ok(None)) => Err(WorkerError::Crashed(match self.child.try_wait() {
+                Ok(Some(status)) => status.to_string(),
+                _ => "output closed".into(),
+            })),
+            Ok(Err(e)) => Err(WorkerError::Crashed(e.to_string())),
+            Err(_) => Err(WorkerError::Timeout),
+        }
+    }
+
+    fn response(line: &str, id: u64) -> Result<Response, WorkerError> {
+        // A bare outcome is accepted as well as a JSON frame.
+        if let Ok(outcome) = line.parse::<Outcome>() {
+            return Ok(Response {
+                outcome,
+                output: String::new(),
+            });
+        }
+
+        let frame = serde_json::from_str::<Value>(line)
+            .map_err(|_| WorkerError::InvalidFrame(format!("\"{}\"", line.trim())))?;
+        match frame.get("id").and_then(Value::as_u64) {
+            Some(frame_id) if frame_id != id => {
+                return Err(WorkerError::InvalidFrame(format!(
+                    "answer to message {} instead of {}",
+                    frame_id, id
+                )))
+            }
+            _ => (),
+        }
+
+        let outcome = frame
+            .get("outcome")
+            .and_then(Value::as_str)
+            .ok_or_else(|| WorkerError::InvalidFrame("missing outcome".into()))?
+            .parse::<Outcome>()
+            .map_err(WorkerError::InvalidFrame)?;
+        let output = match frame.get("output") {
+            Some(Value::String(output)) => output.clone(),
+            Some(Value::Null) | None => String::new(),
+            Some(output) => output.to_string(),
+        };
+
+        Ok(Response { outcome, output })
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // A worker dropped without being stopped doesn't leave its processes behind.
+        if let Ok(None) = self.child.try_wait() {
+            MessageCommand::signal(self.pid, libc::SIGKILL);
+        }
+    }
+}
@@ -3,6 +3,7 @@ mod waiter;
 
 use async_std::sync::{Arc, RwLock};
 
+use crate::client::consumer::handler::MessageHandler;
 use crate::client::consumer::{Consumer, ConsumerError, ConsumerStatus};
 use crate::client::executor::events::{Events, EventsHandler};
 use crate::client::executor::waiter::Waiter;
@@ -40,6 +41,12 @@ impl Executor {
         }
     }
 
+    pub fn add_handler<H: MessageHandler + 'static>(mut self, id: i32, handler: H) -> Self {
+        self.consumer = self.consumer.add_handler(id, handler);
+
+        self
+    }
+
     pub async fn execute(&mut self) -> ExecutorResult<ExecutorStatus> {
         match self.consumer.run().await {
             Ok(ConsumerStatus::CountChanged) => Ok(ExecutorStatus::Restart),
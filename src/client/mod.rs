@@ -6,6 +6,7 @@ use std::time::Duration;
 
 use log::{error, info};
 
+use crate::client::consumer::handler::MessageHandler;
 use crate::client::consumer::ConsumerError;
 use crate::client::executor::{Executor, ExecutorError, ExecutorStatus};
 use crate::config::Config;
@@ -23,6 +24,12 @@ impl Client {
         }
     }
 
+    pub fn add_handler<H: MessageHandler + 'static>(mut self, id: i32, handler: H) -> Self {
+        self.executor = self.executor.add_handler(id, handler);
+
+        self
+    }
+
     pub async fn run(&mut self) -> ClientResult<()> {
         loop {
             match self.executor.execute().await {
@@ -14,5 +14,43 @@ table! {
         retry_wait -> Unsigned<BigInt>,
         retry_mode -> Varchar,
         enabled -> Bool,
+        delivery_mode -> Nullable<Varchar>,
+        env_headers -> Nullable<Varchar>,
+        shell -> Nullable<Bool>,
+        kill_grace -> Nullable<Unsigned<BigInt>>,
+        outcomes -> Nullable<Text>,
+        dead_letter_exchange -> Nullable<Varchar>,
+        dead_letter_queue -> Nullable<Varchar>,
+        max_retries -> Nullable<Integer>,
+        delayed_retry -> Nullable<Bool>,
+        reply_mode -> Nullable<Varchar>,
+        results_exchange -> Nullable<Varchar>,
+        handler -> Nullable<Varchar>,
+        url -> Nullable<Varchar>,
+        script_filename -> Nullable<Varchar>,
+        batch_size -> Nullable<Integer>,
+        batch_timeout -> Nullable<Unsigned<BigInt>>,
+        concurrency -> Nullable<Integer>,
+        working_dir -> Nullable<Varchar>,
+        env -> Nullable<Text>,
+        env_clear -> Nullable<Bool>,
+        uid -> Nullable<Unsigned<BigInt>>,
+        gid -> Nullable<Unsigned<BigInt>>,
+        rlimit_cpu -> Nullable<Unsigned<BigInt>>,
+        rlimit_as -> Nullable<Unsigned<BigInt>>,
+        rlimit_nofile -> Nullable<Unsigned<BigInt>>,
+        charset -> Nullable<Varchar>,
+        max_decompressed_size -> Nullable<Unsigned<BigInt>>,
+        dedup_window -> Nullable<Unsigned<BigInt>>,
+        dedup_key -> Nullable<Varchar>,
+        dedup_path -> Nullable<Varchar>,
+        max_age -> Nullable<Unsigned<BigInt>>,
+        age_header -> Nullable<Varchar>,
+        stale_outcome -> Nullable<Varchar>,
+        schema -> Nullable<Varchar>,
+        output_limit -> Nullable<Unsigned<BigInt>>,
+        output_log -> Nullable<Varchar>,
+        output_log_size -> Nullable<Unsigned<BigInt>>,
+        bindings -> Nullable<Text>,
     }
 }
@@ -11,8 +11,9 @@ use log::info;
 
 use serde::Deserialize;
 
+use crate::config::queue::binding::Arguments;
 use crate::config::queue::config::QueueConfig;
-use crate::utils::{bool_or_string, option_i32_or_string, u16_or_string};
+use crate::utils::{bool_or_string, option_bool_or_string, option_i32_or_string, u16_or_string};
 
 #[derive(Deserialize)]
 pub struct Config {
@@ -31,6 +32,21 @@ pub struct RabbitConfig {
     pub queues: Vec<QueueConfig>,
     pub queue_prefix: String,
     pub reconnections: Option<i32>,
+    #[serde(default)]
+    pub exchanges: Vec<ExchangeConfig>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ExchangeConfig {
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub kind: Option<String>,
+    #[serde(deserialize_with = "option_bool_or_string", default)]
+    pub durable: Option<bool>,
+    #[serde(deserialize_with = "option_bool_or_string", default)]
+    pub auto_delete: Option<bool>,
+    #[serde(default)]
+    pub arguments: Arguments,
 }
 
 #[derive(Deserialize, Clone)]
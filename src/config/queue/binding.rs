@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer};
+
+use diesel::deserialize::{self, FromSql};
+use diesel::mysql::Mysql;
+use diesel::sql_types::Text;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Argument {
+    Bool(bool),
+    Integer(i64),
+    String(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Arguments(BTreeMap<String, Argument>);
+
+impl Arguments {
+    pub fn values(&self) -> &BTreeMap<String, Argument> {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Binding {
+    pub exchange: String,
+    #[serde(default)]
+    pub routing_keys: Vec<String>,
+    #[serde(default)]
+    pub arguments: Arguments,
+}
+
+impl Binding {
+    pub fn routing_keys(&self) -> Vec<&str> {
+        // Headers and fanout exchanges ignore the routing key, a binding needs one anyway.
+        if self.routing_keys.is_empty() {
+            vec![""]
+        } else {
+            self.routing_keys.iter().map(String::as_str).collect()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, FromSqlRow)]
+pub struct Bindings(Vec<Binding>);
+
+impl Bindings {
+    pub fn bindings(&self) -> &[Binding] {
+        &self.0
+    }
+}
+
+impl FromStr for Bindings {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Ok(Bindings::default());
+        }
+
+        serde_json::from_str::<Vec<Binding>>(s)
+            .map(Bindings)
+            .map_err(|e| format!("invalid bindings \"{}\": {}", s.trim(), e))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BindingsOrString {
+    List(Vec<Binding>),
+    Str(String),
+}
+
+impl<'de> Deserialize<'de> for Bindings {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match BindingsOrString::deserialize(deserializer)? {
+            BindingsOrString::List(v) => Ok(Bindings(v)),
+            BindingsOrString::Str(v) => Bindings::from_str(&v).map_err(de::Error::custom),
+        }
+    }
+}
+
+impl FromSql<Text, Mysql> for Bindings {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Mysql>>::from_sql(bytes)?;
+
+        Bindings::from_str(&value).map_err(|e| e.into())
+    }
+}
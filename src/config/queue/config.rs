@@ -1,12 +1,22 @@
 use serde::Deserialize;
 
+use log::warn;
+
 use chrono::{self, NaiveTime};
 
+use crate::config::queue::binding::Bindings;
+use crate::config::queue::environment::Environment;
+use crate::config::queue::outcome::{option_stale_outcome, Outcome, Outcomes};
+use crate::config::queue::{
+    Charset, DEFAULT_BATCH_TIMEOUT, DEFAULT_MAX_DECOMPRESSED_SIZE, DEFAULT_OUTPUT_LIMIT,
+    DEFAULT_OUTPUT_LOG_SIZE, DEFAULT_RETRY_TIERS, MAX_RETRY_TIERS, TIME_MS_MULTIPLIER,
+};
 use crate::utils::{
-    bool_or_string, i32_or_string, option_i32_or_string, option_u64_or_string, u64_or_string,
+    bool_or_string, i32_or_string, option_bool_or_string, option_i32_or_string,
+    option_u64_or_string, u64_or_string,
 };
 
-#[derive(Queryable, Deserialize, Debug, Clone)]
+#[derive(Queryable, Deserialize, Debug, Clone, Default)]
 pub struct QueueConfig {
     #[serde(deserialize_with = "i32_or_string")]
     pub id: i32,
@@ -30,4 +40,153 @@ pub struct QueueConfig {
     pub retry_mode: String,
     #[serde(deserialize_with = "bool_or_string")]
     pub enabled: bool,
+    #[serde(default)]
+    pub delivery_mode: Option<String>,
+    #[serde(default)]
+    pub env_headers: Option<String>,
+    #[serde(deserialize_with = "option_bool_or_string", default)]
+    pub shell: Option<bool>,
+    #[serde(deserialize_with = "option_u64_or_string", default)]
+    pub kill_grace: Option<u64>,
+    #[serde(default)]
+    pub outcomes: Option<Outcomes>,
+    #[serde(default)]
+    pub dead_letter_exchange: Option<String>,
+    #[serde(default)]
+    pub dead_letter_queue: Option<String>,
+    #[serde(deserialize_with = "option_i32_or_string", default)]
+    pub max_retries: Option<i32>,
+    #[serde(deserialize_with = "option_bool_or_string", default)]
+    pub delayed_retry: Option<bool>,
+    #[serde(default)]
+    pub reply_mode: Option<String>,
+    #[serde(default)]
+    pub results_exchange: Option<String>,
+    #[serde(default)]
+    pub handler: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub script_filename: Option<String>,
+    #[serde(deserialize_with = "option_i32_or_string", default)]
+    pub batch_size: Option<i32>,
+    #[serde(deserialize_with = "option_u64_or_string", default)]
+    pub batch_timeout: Option<u64>,
+    #[serde(deserialize_with = "option_i32_or_string", default)]
+    pub concurrency: Option<i32>,
+    #[serde(default)]
+    pub working_dir: Option<String>,
+    #[serde(default)]
+    pub env: Option<Environment>,
+    #[serde(deserialize_with = "option_bool_or_string", default)]
+    pub env_clear: Option<bool>,
+    #[serde(deserialize_with = "option_u64_or_string", default)]
+    pub uid: Option<u64>,
+    #[serde(deserialize_with = "option_u64_or_string", default)]
+    pub gid: Option<u64>,
+    #[serde(deserialize_with = "option_u64_or_string", default)]
+    pub rlimit_cpu: Option<u64>,
+    #[serde(deserialize_with = "option_u64_or_string", default)]
+    pub rlimit_as: Option<u64>,
+    #[serde(deserialize_with = "option_u64_or_string", default)]
+    pub rlimit_nofile: Option<u64>,
+    #[serde(default)]
+    pub charset: Option<Charset>,
+    #[serde(deserialize_with = "option_u64_or_string", default)]
+    pub max_decompressed_size: Option<u64>,
+    #[serde(deserialize_with = "option_u64_or_string", default)]
+    pub dedup_window: Option<u64>,
+    #[serde(default)]
+    pub dedup_key: Option<String>,
+    #[serde(default)]
+    pub dedup_path: Option<String>,
+    #[serde(deserialize_with = "option_u64_or_string", default)]
+    pub max_age: Option<u64>,
+    #[serde(default)]
+    pub age_header: Option<String>,
+    #[serde(deserialize_with = "option_stale_outcome", default)]
+    pub stale_outcome: Option<Outcome>,
+    #[serde(default)]
+    pub schema: Option<String>,
+    #[serde(deserialize_with = "option_u64_or_string", default)]
+    pub output_limit: Option<u64>,
+    #[serde(default)]
+    pub output_log: Option<String>,
+    #[serde(deserialize_with = "option_u64_or_string", default)]
+    pub output_log_size: Option<u64>,
+    #[serde(default)]
+    pub bindings: Option<Bindings>,
+}
+
+impl QueueConfig {
+    pub fn has_dead_letter(&self) -> bool {
+        self.dead_letter_exchange.is_some() || self.dead_letter_queue.is_some()
+    }
+
+    pub fn has_delayed_retry(&self) -> bool {
+        self.delayed_retry.unwrap_or(false)
+    }
+
+    pub fn has_batch(&self) -> bool {
+        self.batch_limit() > 1
+    }
+
+    pub fn batch_limit(&self) -> usize {
+        self.batch_size.unwrap_or(1).max(1) as usize
+    }
+
+    pub fn batch_wait(&self) -> u64 {
+        self.batch_timeout.unwrap_or(DEFAULT_BATCH_TIMEOUT)
+    }
+
+    pub fn concurrency_limit(&self) -> usize {
+        self.concurrency.unwrap_or(1).max(1) as usize
+    }
+
+    pub fn has_dedup(&self) -> bool {
+        self.dedup_window.unwrap_or(0) > 0
+    }
+
+    pub fn stale_outcome(&self) -> Outcome {
+        // The configuration file can't hold an unsupported outcome, a database row can.
+        match self.stale_outcome.map(Outcome::stale) {
+            Some(Ok(outcome)) => outcome,
+            Some(Err(e)) => {
+                warn!(
+                    "[{}] {}, stale messages are acknowledged.",
+                    self.queue_name, e
+                );
+
+                Outcome::Ack
+            }
+            None => Outcome::Ack,
+        }
+    }
+
+    pub fn decompress_limit(&self) -> usize {
+        self.max_decompressed_size
+            .unwrap_or(DEFAULT_MAX_DECOMPRESSED_SIZE) as usize
+    }
+
+    pub fn output_limit(&self) -> usize {
+        self.output_limit.unwrap_or(DEFAULT_OUTPUT_LIMIT) as usize
+    }
+
+    pub fn output_log_size(&self) -> u64 {
+        self.output_log_size.unwrap_or(DEFAULT_OUTPUT_LOG_SIZE)
+    }
+
+    pub fn retry_delays(&self) -> Vec<u64> {
+        let wait = self.retry_wait * TIME_MS_MULTIPLIER;
+
+        match self.retry_mode.as_str() {
+            "incremental" => (0..self
+                .max_retries
+                .unwrap_or(DEFAULT_RETRY_TIERS)
+                .clamp(1, MAX_RETRY_TIERS))
+                .map(|tier| wait.saturating_mul(1 << tier))
+                .collect(),
+            _ => vec![wait],
+        }
+    }
 }
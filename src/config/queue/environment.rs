@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer};
+
+use diesel::deserialize::{self, FromSql};
+use diesel::mysql::Mysql;
+use diesel::sql_types::Text;
+
+#[derive(Debug, Clone, Default, PartialEq, FromSqlRow)]
+pub struct Environment(Vec<(String, String)>);
+
+impl Environment {
+    pub fn variables(&self) -> &[(String, String)] {
+        &self.0
+    }
+
+    fn from_pairs<I, S>(pairs: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = (S, S)>,
+        S: AsRef<str>,
+    {
+        pairs
+            .into_iter()
+            .map(|(name, value)| {
+                let name = name.as_ref().trim();
+                if name.is_empty() || name.contains('=') || name.contains('\0') {
+                    return Err(format!("invalid environment variable \"{}\"", name));
+                }
+
+                Ok((name.to_string(), value.as_ref().to_string()))
+            })
+            .collect::<Result<Vec<(String, String)>, String>>()
+            .map(Environment)
+    }
+}
+
+impl FromStr for Environment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_pairs(
+            s.split(',')
+                .filter(|pair| !pair.trim().is_empty())
+                .map(|pair| match pair.find('=') {
+                    Some(position) => Ok((&pair[..position], &pair[position + 1..])),
+                    None => Err(format!("invalid environment variable \"{}\"", pair.trim())),
+                })
+                .collect::<Result<Vec<(&str, &str)>, String>>()?,
+        )
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EnvironmentOrString {
+    Table(BTreeMap<String, String>),
+    Str(String),
+}
+
+impl<'de> Deserialize<'de> for Environment {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match EnvironmentOrString::deserialize(deserializer)? {
+            EnvironmentOrString::Table(v) => Self::from_pairs(v).map_err(de::Error::custom),
+            EnvironmentOrString::Str(v) => Environment::from_str(&v).map_err(de::Error::custom),
+        }
+    }
+}
+
+impl FromSql<Text, Mysql> for Environment {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Mysql>>::from_sql(bytes)?;
+
+        Environment::from_str(&value).map_err(|e| e.into())
+    }
+}
@@ -1,7 +1,17 @@
+pub mod binding;
 pub mod config;
+pub mod environment;
 pub mod model;
+pub mod outcome;
 
 use std::collections::HashMap;
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer};
+
+use diesel::deserialize::{self, FromSql};
+use diesel::mysql::Mysql;
+use diesel::sql_types::Text;
 
 use crate::config::queue::config::QueueConfig;
 use crate::config::queue::model::QueueModel;
@@ -11,6 +21,51 @@ pub const TIME_S_MULTIPLIER: u64 = 60;
 
 pub const DEFAULT_WAIT: u64 = 120;
 pub const DEFAULT_TIMEOUT: u64 = 30;
+pub const DEFAULT_KILL_GRACE: u64 = 10;
+pub const DEFAULT_RETRY_TIERS: i32 = 5;
+pub const MAX_RETRY_TIERS: i32 = 10;
+pub const DEFAULT_BATCH_TIMEOUT: u64 = 1000;
+pub const DEFAULT_MAX_DECOMPRESSED_SIZE: u64 = 64 * 1024 * 1024;
+pub const DEFAULT_OUTPUT_LIMIT: u64 = 1024 * 1024;
+pub const DEFAULT_OUTPUT_LOG_SIZE: u64 = 10 * 1024 * 1024;
+
+const RETRY_QUEUE_SUFFIX: &str = ".retry.";
+
+// The characters of the 0x80-0x9f range, where Windows-1252 differs from Latin-1.
+const WINDOWS_1252: [Option<char>; 32] = [
+    Some('\u{20ac}'),
+    None,
+    Some('\u{201a}'),
+    Some('\u{0192}'),
+    Some('\u{201e}'),
+    Some('\u{2026}'),
+    Some('\u{2020}'),
+    Some('\u{2021}'),
+    Some('\u{02c6}'),
+    Some('\u{2030}'),
+    Some('\u{0160}'),
+    Some('\u{2039}'),
+    Some('\u{0152}'),
+    None,
+    Some('\u{017d}'),
+    None,
+    None,
+    Some('\u{2018}'),
+    Some('\u{2019}'),
+    Some('\u{201c}'),
+    Some('\u{201d}'),
+    Some('\u{2022}'),
+    Some('\u{2013}'),
+    Some('\u{2014}'),
+    Some('\u{02dc}'),
+    Some('\u{2122}'),
+    Some('\u{0161}'),
+    Some('\u{203a}'),
+    Some('\u{0153}'),
+    None,
+    Some('\u{017e}'),
+    Some('\u{0178}'),
+];
 
 pub enum RetryType {
     Static,
@@ -24,6 +79,95 @@ pub enum RetryMode {
     Forced,
 }
 
+pub enum DeliveryMode {
+    Argv,
+    Stdin,
+    Tempfile,
+}
+
+// The TTL is part of the name because the broker can't change the arguments of an existing
+// queue: a new delay gets a new retry queue.
+pub fn retry_queue_name<S: AsRef<str>>(queue_name: S, delay: u64) -> String {
+    format!("{}{}{}ms", queue_name.as_ref(), RETRY_QUEUE_SUFFIX, delay)
+}
+
+pub enum HandlerType {
+    Command,
+    Http,
+    FastCgi,
+    Worker,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, FromSqlRow)]
+pub enum Charset {
+    Utf8,
+    Latin1,
+    Windows1252,
+    Ascii,
+}
+
+impl Charset {
+    pub fn decode(&self, data: &[u8]) -> Option<String> {
+        match self {
+            Charset::Utf8 => String::from_utf8(data.to_vec()).ok(),
+            Charset::Latin1 => Some(data.iter().map(|byte| *byte as char).collect()),
+            Charset::Windows1252 => data
+                .iter()
+                .map(|byte| match byte {
+                    0x80..=0x9f => WINDOWS_1252[(byte - 0x80) as usize],
+                    _ => Some(*byte as char),
+                })
+                .collect(),
+            Charset::Ascii => data
+                .iter()
+                .map(|byte| {
+                    if byte.is_ascii() {
+                        Some(*byte as char)
+                    } else {
+                        None
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+impl FromStr for Charset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Charset::Utf8),
+            "iso-8859-1" | "latin1" | "latin-1" => Ok(Charset::Latin1),
+            "windows-1252" | "cp1252" => Ok(Charset::Windows1252),
+            "us-ascii" | "ascii" => Ok(Charset::Ascii),
+            charset => Err(format!("unknown charset \"{}\"", charset)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Charset {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Charset::from_str(&String::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
+
+impl FromSql<Text, Mysql> for Charset {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Mysql>>::from_sql(bytes)?;
+
+        Charset::from_str(&value).map_err(|e| e.into())
+    }
+}
+
+pub enum ReplyMode {
+    Raw,
+    Json,
+}
+
 pub struct Queue {
     inner: Box<dyn QueueModel>,
     waits: HashMap<(i32, i32), u64>,
@@ -49,6 +193,10 @@ impl Queue {
         self.inner.get_command_timeout(id)
     }
 
+    pub fn get_kill_grace(&mut self, id: i32) -> u64 {
+        self.inner.get_kill_grace(id)
+    }
+
     pub fn is_enabled(&mut self, id: i32) -> bool {
         self.inner.is_enabled(id)
     }
@@ -68,6 +216,54 @@ impl Queue {
         }
     }
 
+    pub fn get_delivery_mode(&mut self, id: i32) -> DeliveryMode {
+        match self.inner.get_queue(id) {
+            Some(queue) => match queue.delivery_mode.as_deref() {
+                Some("stdin") => DeliveryMode::Stdin,
+                Some("tempfile") => DeliveryMode::Tempfile,
+                _ => DeliveryMode::Argv,
+            },
+            None => DeliveryMode::Argv,
+        }
+    }
+
+    pub fn get_charset(&mut self, id: i32) -> Option<Charset> {
+        match self.inner.get_queue(id) {
+            Some(queue) => queue.charset,
+            None => None,
+        }
+    }
+
+    pub fn get_handler_type(&mut self, id: i32) -> HandlerType {
+        match self.inner.get_queue(id) {
+            Some(queue) => match queue.handler.as_deref() {
+                Some("http") => HandlerType::Http,
+                Some("fastcgi") => HandlerType::FastCgi,
+                Some("worker") => HandlerType::Worker,
+                _ => HandlerType::Command,
+            },
+            None => HandlerType::Command,
+        }
+    }
+
+    pub fn get_url(&mut self, id: i32) -> String {
+        match self.inner.get_queue(id) {
+            Some(queue) => queue.url.unwrap_or_default(),
+            None => String::new(),
+        }
+    }
+
+    pub fn get_reply_mode(&mut self, id: i32) -> Option<ReplyMode> {
+        match self.inner.get_queue(id) {
+            Some(queue) => match queue.reply_mode.as_deref() {
+                Some("raw") => Some(ReplyMode::Raw),
+                Some("json") => Some(ReplyMode::Json),
+                _ => None,
+            },
+            None => None,
+        }
+    }
+
     pub fn get_queue_wait(&mut self, id: i32, consumer_index: i32) -> u64 {
         let inner = &mut self.inner;
         *self
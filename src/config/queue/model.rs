@@ -19,6 +19,13 @@ pub trait QueueModel: Send + Sync {
             * super::TIME_MS_MULTIPLIER
     }
 
+    fn get_kill_grace(&mut self, id: i32) -> u64 {
+        (match self.get_queue(id) {
+            Some(queue) => queue.kill_grace.unwrap_or(super::DEFAULT_KILL_GRACE),
+            None => super::DEFAULT_KILL_GRACE,
+        }) * super::TIME_MS_MULTIPLIER
+    }
+
     fn is_enabled(&mut self, id: i32) -> bool {
         match self.get_queue(id) {
             Some(queue) => {
@@ -0,0 +1,279 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer};
+
+use diesel::deserialize::{self, FromSql};
+use diesel::mysql::Mysql;
+use diesel::sql_types::Text;
+
+const ACKNOWLEDGEMENT: i32 = 0;
+const NEGATIVE_ACKNOWLEDGEMENT_AND_RE_QUEUE: i32 = 1;
+const NEGATIVE_ACKNOWLEDGEMENT: i32 = 2;
+
+const HTTP_REQUEST_TIMEOUT: u16 = 408;
+const HTTP_TOO_MANY_REQUESTS: u16 = 429;
+
+const TIMEOUT: &str = "timeout";
+const ERROR: &str = "error";
+const SIGNAL_PREFIX: &str = "SIG";
+
+const SIGNALS: [(&str, i32); 15] = [
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, FromSqlRow)]
+pub enum Outcome {
+    Ack,
+    Requeue,
+    Retry,
+    Reject,
+    DeadLetter,
+}
+
+impl FromStr for Outcome {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "ack" => Ok(Outcome::Ack),
+            "requeue" => Ok(Outcome::Requeue),
+            "retry" => Ok(Outcome::Retry),
+            "reject" => Ok(Outcome::Reject),
+            "dead-letter" | "dead_letter" => Ok(Outcome::DeadLetter),
+            outcome => Err(format!("unknown outcome \"{}\"", outcome)),
+        }
+    }
+}
+
+impl Outcome {
+    // Stale messages are never handled, so they can't be retried: they are only dropped,
+    // rejected or dead-lettered.
+    pub fn stale(self) -> Result<Self, String> {
+        match self {
+            Outcome::Ack | Outcome::Reject | Outcome::DeadLetter => Ok(self),
+            outcome => Err(format!(
+                "outcome \"{}\" not supported for stale messages",
+                outcome
+            )),
+        }
+    }
+}
+
+pub fn option_stale_outcome<'de, D>(deserializer: D) -> Result<Option<Outcome>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Outcome::from_str(&String::deserialize(deserializer)?)
+        .and_then(Outcome::stale)
+        .map(Some)
+        .map_err(de::Error::custom)
+}
+
+impl FromSql<Text, Mysql> for Outcome {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Mysql>>::from_sql(bytes)?;
+
+        Outcome::from_str(&value).map_err(|e| e.into())
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Outcome::Ack => "ack",
+                Outcome::Requeue => "requeue",
+                Outcome::Retry => "retry",
+                Outcome::Reject => "reject",
+                Outcome::DeadLetter => "dead-letter",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum OutcomeCase {
+    Code(i32),
+    Codes(i32, i32),
+    Signal(i32),
+    Timeout,
+    Error,
+}
+
+impl FromStr for OutcomeCase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let case = s.trim();
+        let invalid = || format!("invalid exit code \"{}\"", case);
+
+        match case {
+            TIMEOUT => Ok(OutcomeCase::Timeout),
+            ERROR => Ok(OutcomeCase::Error),
+            _ if case.starts_with(SIGNAL_PREFIX) => {
+                let name = &case[SIGNAL_PREFIX.len()..];
+                match SIGNALS.iter().find(|(signal, _)| *signal == name) {
+                    Some((_, signal)) => Ok(OutcomeCase::Signal(*signal)),
+                    None => name
+                        .parse::<i32>()
+                        .map(OutcomeCase::Signal)
+                        .map_err(|_| format!("unknown signal \"{}\"", case)),
+                }
+            }
+            _ => match case.find('-') {
+                Some(position) if position > 0 => {
+                    let from = case[..position].trim().parse().map_err(|_| invalid())?;
+                    let to = case[position + 1..].trim().parse().map_err(|_| invalid())?;
+
+                    Ok(OutcomeCase::Codes(from, to))
+                }
+                _ => case.parse().map(OutcomeCase::Code).map_err(|_| invalid()),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, FromSqlRow)]
+pub struct Outcomes(Vec<(OutcomeCase, Outcome)>);
+
+impl Outcomes {
+    pub fn exit(&self, status: &ExitStatus, nack_code: Option<i32>) -> Outcome {
+        let configured = match (status.code(), status.signal()) {
+            (Some(code), _) => self.code(code),
+            (None, Some(signal)) => self.find(|case| *case == OutcomeCase::Signal(signal)),
+            (None, None) => None,
+        };
+
+        configured.unwrap_or_else(|| {
+            let exit_code = status.code().unwrap_or(NEGATIVE_ACKNOWLEDGEMENT);
+
+            let exit_code = if let Some(nack_code) = nack_code {
+                if nack_code == exit_code {
+                    NEGATIVE_ACKNOWLEDGEMENT
+                } else if exit_code == ACKNOWLEDGEMENT {
+                    exit_code
+                } else {
+                    NEGATIVE_ACKNOWLEDGEMENT_AND_RE_QUEUE
+                }
+            } else {
+                exit_code
+            };
+
+            match exit_code {
+                ACKNOWLEDGEMENT => Outcome::Ack,
+                NEGATIVE_ACKNOWLEDGEMENT_AND_RE_QUEUE => Outcome::Retry,
+                _ => Outcome::DeadLetter,
+            }
+        })
+    }
+
+    pub fn status(&self, status: u16) -> Outcome {
+        self.code(status as i32).unwrap_or(match status {
+            200..=299 => Outcome::Ack,
+            HTTP_REQUEST_TIMEOUT | HTTP_TOO_MANY_REQUESTS | 500..=599 => Outcome::Retry,
+            _ => Outcome::DeadLetter,
+        })
+    }
+
+    pub fn timeout(&self) -> Outcome {
+        self.find(|case| *case == OutcomeCase::Timeout)
+            .unwrap_or(Outcome::Requeue)
+    }
+
+    pub fn error(&self) -> Outcome {
+        self.find(|case| *case == OutcomeCase::Error)
+            .unwrap_or(Outcome::DeadLetter)
+    }
+
+    fn code(&self, code: i32) -> Option<Outcome> {
+        self.find(|case| *case == OutcomeCase::Code(code))
+            .or_else(|| {
+                self.find(|case| match case {
+                    OutcomeCase::Codes(from, to) => *from <= code && code <= *to,
+                    _ => false,
+                })
+            })
+    }
+
+    fn find<P: Fn(&OutcomeCase) -> bool>(&self, predicate: P) -> Option<Outcome> {
+        self.0
+            .iter()
+            .find(|(case, _)| predicate(case))
+            .map(|(_, outcome)| *outcome)
+    }
+
+    fn from_pairs<I, S>(pairs: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = (S, S)>,
+        S: AsRef<str>,
+    {
+        pairs
+            .into_iter()
+            .map(|(case, outcome)| Ok((case.as_ref().parse()?, outcome.as_ref().parse()?)))
+            .collect::<Result<Vec<(OutcomeCase, Outcome)>, String>>()
+            .map(Outcomes)
+    }
+}
+
+impl FromStr for Outcomes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_pairs(
+            s.split(',')
+                .filter(|pair| !pair.trim().is_empty())
+                .map(|pair| match pair.find('=') {
+                    Some(position) => Ok((&pair[..position], &pair[position + 1..])),
+                    None => Err(format!("invalid outcome \"{}\"", pair.trim())),
+                })
+                .collect::<Result<Vec<(&str, &str)>, String>>()?,
+        )
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OutcomesOrString {
+    Table(BTreeMap<String, String>),
+    Str(String),
+}
+
+impl<'de> Deserialize<'de> for Outcomes {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match OutcomesOrString::deserialize(deserializer)? {
+            OutcomesOrString::Table(v) => Self::from_pairs(v).map_err(de::Error::custom),
+            OutcomesOrString::Str(v) => Outcomes::from_str(&v).map_err(de::Error::custom),
+        }
+    }
+}
+
+impl FromSql<Text, Mysql> for Outcomes {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Mysql>>::from_sql(bytes)?;
+
+        Outcomes::from_str(&value).map_err(|e| e.into())
+    }
+}
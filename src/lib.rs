@@ -1,5 +1,3 @@
-#![feature(static_nobundle)]
-
 #[macro_use]
 extern crate diesel;
 
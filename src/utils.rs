@@ -20,6 +20,16 @@ where
     }
 }
 
+pub fn option_bool_or_string<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match bool_or_string(deserializer) {
+        Ok(value) => Ok(Some(value)),
+        _ => Ok(None),
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(untagged)]
 enum I32OrString {
@@ -105,6 +115,17 @@ where
     }
 }
 
+pub fn hostname() -> String {
+    let mut buffer = [0u8; 256];
+    if unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) } != 0 {
+        return String::new();
+    }
+
+    let end = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+
+    String::from_utf8_lossy(&buffer[..end]).to_string()
+}
+
 pub async fn wait(millis: u64) {
     sleep(Duration::from_millis(millis)).await
 }
@@ -0,0 +1,118 @@
+use lapin::options::ExchangeDeclareOptions;
+use lapin::types::{AMQPValue, LongString, ShortString};
+use lapin::ExchangeKind;
+
+use rabbitmq_consumer_lib::client::consumer::channel::Channel;
+use rabbitmq_consumer_lib::config::RabbitConfig;
+
+mod common;
+
+use common::queue;
+
+fn rabbit() -> RabbitConfig {
+    toml::from_str::<RabbitConfig>(
+        r#"
+            host = "127.0.0.1"
+            port = 5672
+            username = "guest"
+            password = "guest"
+            vhost = "/"
+            queue_prefix = "queue_"
+            queues = []
+
+            [[exchanges]]
+            name = "events"
+
+            [[exchanges]]
+            name = "invoices"
+            type = "headers"
+            auto_delete = true
+
+            [[exchanges]]
+            name = "delayed"
+            type = "x-delayed-message"
+            durable = false
+            arguments = { "x-delayed-type" = "topic", "x-max-length" = 10, "internal" = true }
+        "#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn exchanges() {
+    let exchanges = rabbit().exchanges;
+
+    // The exchanges are direct and durable by default.
+    assert_eq!(Channel::exchange_kind(&exchanges[0]), ExchangeKind::Direct);
+    assert_eq!(
+        Channel::exchange_options(&exchanges[0]),
+        ExchangeDeclareOptions {
+            durable: true,
+            auto_delete: false,
+            ..Default::default()
+        }
+    );
+    assert!(Channel::arguments(&exchanges[0].arguments)
+        .inner()
+        .is_empty());
+
+    assert_eq!(Channel::exchange_kind(&exchanges[1]), ExchangeKind::Headers);
+    assert!(Channel::exchange_options(&exchanges[1]).auto_delete);
+
+    // Plugin types are declared as they are, with their typed arguments.
+    assert_eq!(
+        Channel::exchange_kind(&exchanges[2]),
+        ExchangeKind::Custom("x-delayed-message".into())
+    );
+    assert!(!Channel::exchange_options(&exchanges[2]).durable);
+
+    let arguments = Channel::arguments(&exchanges[2].arguments);
+    let arguments = arguments.inner();
+    assert_eq!(arguments.len(), 3);
+    assert_eq!(
+        arguments.get(&ShortString::from("x-delayed-type")),
+        Some(&AMQPValue::LongString(LongString::from("topic")))
+    );
+    assert_eq!(
+        arguments.get(&ShortString::from("x-max-length")),
+        Some(&AMQPValue::LongLongInt(10))
+    );
+    assert_eq!(
+        arguments.get(&ShortString::from("internal")),
+        Some(&AMQPValue::Boolean(true))
+    );
+}
+
+#[test]
+fn bindings() {
+    let queue = queue(
+        r#"
+            bindings = [
+                { exchange = "events", routing_keys = ["user.created", "user.deleted"] },
+                { exchange = "invoices", arguments = { "x-match" = "all", type = "invoice" } },
+            ]
+        "#,
+    );
+    let bindings = queue.bindings.unwrap();
+    let bindings = bindings.bindings();
+
+    // One binding for each routing key, or a single one without routing key.
+    assert_eq!(
+        bindings[0].routing_keys(),
+        vec!["user.created", "user.deleted"]
+    );
+    assert!(Channel::arguments(&bindings[0].arguments)
+        .inner()
+        .is_empty());
+
+    assert_eq!(bindings[1].routing_keys(), vec![""]);
+    let arguments = Channel::arguments(&bindings[1].arguments);
+    assert_eq!(
+        arguments.inner().get(&ShortString::from("x-match")),
+        Some(&AMQPValue::LongString(LongString::from("all")))
+    );
+    assert_eq!(
+        arguments.inner().get(&ShortString::from("type")),
+        Some(&AMQPValue::LongString(LongString::from("invoice")))
+    );
+}
@@ -1,34 +1,41 @@
-use async_std::sync::{Arc, RwLock};
+use std::fs;
+use std::io;
+use std::time::Duration;
 
-use lapin::options::BasicPublishOptions;
+use async_std::sync::{Arc, Mutex, RwLock};
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+
+use lapin::options::{BasicGetOptions, BasicPublishOptions, QueueDeclareOptions};
+use lapin::types::{AMQPValue, FieldTable};
 use lapin::BasicProperties;
-use lapin::Connection as LapinConnection;
+use lapin::{Channel as LapinChannel, Connection as LapinConnection};
 
 use rabbitmq_consumer_lib::client::consumer::channel::Channel;
 use rabbitmq_consumer_lib::client::consumer::connection::{Connection, ConnectionError};
+use rabbitmq_consumer_lib::client::consumer::handler::{Handled, HandlerContext, MessageHandler};
 use rabbitmq_consumer_lib::client::consumer::{Consumer, ConsumerStatus};
 use rabbitmq_consumer_lib::config::file::File;
+use rabbitmq_consumer_lib::config::queue::binding::Bindings;
 use rabbitmq_consumer_lib::config::queue::config::QueueConfig;
+use rabbitmq_consumer_lib::config::queue::outcome::Outcome;
 use rabbitmq_consumer_lib::config::queue::Queue;
-use rabbitmq_consumer_lib::config::{Config, DatabaseConfig, RabbitConfig};
+use rabbitmq_consumer_lib::config::{Config, DatabaseConfig, ExchangeConfig, RabbitConfig};
 
 fn get_queues() -> Vec<QueueConfig> {
     vec![
         QueueConfig {
             id: 1,
-            prefetch_count: None,
             queue_name: "example".into(),
             consumer_name: "example".into(),
             command: "echo 1".into(),
-            command_timeout: None,
             base64: false,
-            start_hour: None,
-            end_hour: None,
             count: 100,
-            nack_code: None,
             retry_wait: 120,
             retry_mode: "static".into(),
             enabled: true,
+            ..Default::default()
         },
         QueueConfig {
             id: 2,
@@ -36,35 +43,49 @@ fn get_queues() -> Vec<QueueConfig> {
             queue_name: "example2".into(),
             consumer_name: "example".into(),
             command: "echo 1".into(),
-            command_timeout: None,
             base64: false,
-            start_hour: None,
-            end_hour: None,
             count: 100,
-            nack_code: None,
             retry_wait: 120,
             retry_mode: "ignored".into(),
             enabled: false,
+            ..Default::default()
         },
         QueueConfig {
             id: 3,
-            prefetch_count: None,
             queue_name: "example3".into(),
             consumer_name: "example".into(),
             command: "echo 1".into(),
-            command_timeout: None,
             base64: true,
-            start_hour: None,
-            end_hour: None,
             count: 100,
-            nack_code: None,
             retry_wait: 120,
             retry_mode: "incremental".into(),
             enabled: true,
+            ..Default::default()
         },
     ]
 }
 
+struct Recorder {
+    received: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl Recorder {
+    async fn record(&self, context: &HandlerContext<'_>) -> io::Result<Handled> {
+        self.received
+            .lock()
+            .await
+            .push(context.delivery.data.clone());
+
+        Ok(Handled::from(Outcome::Ack))
+    }
+}
+
+impl MessageHandler for Recorder {
+    fn handle<'a>(&'a self, context: &'a HandlerContext<'a>) -> BoxFuture<'a, io::Result<Handled>> {
+        self.record(context).boxed()
+    }
+}
+
 fn get_cfg() -> Config {
     Config {
         rabbit: RabbitConfig {
@@ -76,6 +97,7 @@ fn get_cfg() -> Config {
             queues: get_queues(),
             queue_prefix: "sample_".into(),
             reconnections: Some(0),
+            exchanges: vec![],
         },
         database: DatabaseConfig {
             enabled: false,
@@ -112,6 +134,65 @@ async fn connect(config: Config) -> Result<Arc<LapinConnection>, ConnectionError
     Connection::new(config.rabbit).get_connection().await
 }
 
+async fn consume_bodies<F: FnOnce(&mut QueueConfig)>(
+    configure: F,
+    bodies: &[&str],
+    seconds: u64,
+) -> LapinChannel {
+    let messages = bodies
+        .iter()
+        .map(|body| (*body, BasicProperties::default()))
+        .collect::<Vec<_>>();
+
+    consume_messages(configure, &messages, seconds).await
+}
+
+// Runs a consumer for the first queue, configured by the closure, on the given messages
+// and returns a channel to inspect the queues afterwards.
+async fn consume_messages<F: FnOnce(&mut QueueConfig)>(
+    configure: F,
+    messages: &[(&str, BasicProperties)],
+    seconds: u64,
+) -> LapinChannel {
+    let mut config = create_config();
+    configure(&mut config.rabbit.queues[0]);
+    let queue_config = config.rabbit.queues[0].clone();
+
+    let connection = connect(create_config()).await.unwrap();
+    let (channel, queue) = Channel::get_queue(
+        connection.clone(),
+        queue_config.clone(),
+        config.rabbit.queue_prefix.clone(),
+        &config.rabbit.exchanges,
+    )
+    .await
+    .unwrap();
+
+    for (body, properties) in messages {
+        channel
+            .basic_publish(
+                "",
+                queue.name().as_str(),
+                BasicPublishOptions::default(),
+                body.as_bytes().to_vec(),
+                properties.clone(),
+            )
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+    }
+
+    let consumer = Consumer::new(config);
+    let _ = tokio::time::timeout(
+        Duration::from_secs(seconds),
+        consumer.consume(0, queue_config, channel, queue),
+    )
+    .await;
+
+    connection.create_channel().await.unwrap()
+}
+
 #[tokio::test]
 async fn connection() {
     let connection = connect(create_config()).await;
@@ -130,12 +211,146 @@ async fn channel_queue() {
         connection,
         data.write().await.get_queues().get(0).unwrap().to_owned(),
         config.rabbit.queue_prefix.clone(),
+        &config.rabbit.exchanges,
     )
     .await;
 
     assert!(result.is_ok());
 }
 
+#[tokio::test]
+async fn channel_results_exchange() {
+    let mut config = create_config();
+    config.rabbit.exchanges = vec![ExchangeConfig {
+        name: "sample_results_fanout".into(),
+        kind: Some("fanout".into()),
+        durable: None,
+        auto_delete: None,
+        arguments: Default::default(),
+    }];
+    let queue_config = QueueConfig {
+        results_exchange: Some("sample_results_fanout".into()),
+        ..config.rabbit.queues[0].clone()
+    };
+
+    let connection = connect(create_config()).await.unwrap();
+    Channel::declare_exchanges(connection.clone(), &config.rabbit.exchanges)
+        .await
+        .unwrap();
+
+    // A configured results exchange keeps its own type.
+    assert!(Channel::get_queue(
+        connection.clone(),
+        queue_config.clone(),
+        config.rabbit.queue_prefix.clone(),
+        &config.rabbit.exchanges,
+    )
+    .await
+    .is_ok());
+
+    // Otherwise it's declared as a topic exchange, which conflicts with the existing one.
+    assert!(Channel::get_queue(
+        connection,
+        queue_config,
+        config.rabbit.queue_prefix.clone(),
+        &[],
+    )
+    .await
+    .is_err());
+}
+
+#[tokio::test]
+async fn channel_bindings() {
+    let mut config = create_config();
+    config.rabbit.exchanges = vec![
+        ExchangeConfig {
+            name: "sample_events".into(),
+            kind: Some("topic".into()),
+            durable: None,
+            auto_delete: None,
+            arguments: Default::default(),
+        },
+        ExchangeConfig {
+            name: "sample_invoices".into(),
+            kind: Some("headers".into()),
+            durable: None,
+            auto_delete: None,
+            arguments: Default::default(),
+        },
+    ];
+    let queue_config = QueueConfig {
+        queue_name: "example_bindings".into(),
+        bindings: Some(
+            r#"[
+                {"exchange":"sample_events","routing_keys":["user.created","user.deleted"]},
+                {"exchange":"sample_invoices","arguments":{"x-match":"all","type":"invoice"}}
+            ]"#
+            .parse::<Bindings>()
+            .unwrap(),
+        ),
+        ..config.rabbit.queues[0].clone()
+    };
+
+    let connection = connect(create_config()).await.unwrap();
+    Channel::declare_exchanges(connection.clone(), &config.rabbit.exchanges)
+        .await
+        .unwrap();
+
+    // Each consumer of the queue declares the same bindings again.
+    for _ in 0..2 {
+        Channel::get_queue(
+            connection.clone(),
+            queue_config.clone(),
+            config.rabbit.queue_prefix.clone(),
+            &config.rabbit.exchanges,
+        )
+        .await
+        .unwrap();
+    }
+
+    let channel = connection.create_channel().await.unwrap();
+    channel
+        .queue_purge("sample_example_bindings", Default::default())
+        .await
+        .unwrap();
+
+    let mut invoice = FieldTable::default();
+    invoice.insert("type".into(), AMQPValue::LongString("invoice".into()));
+    let mut receipt = FieldTable::default();
+    receipt.insert("type".into(), AMQPValue::LongString("receipt".into()));
+    for (exchange, routing_key, headers) in &[
+        ("sample_events", "user.created", FieldTable::default()),
+        ("sample_events", "user.deleted", FieldTable::default()),
+        ("sample_events", "user.updated", FieldTable::default()),
+        ("sample_invoices", "", invoice),
+        ("sample_invoices", "", receipt),
+    ] {
+        channel
+            .basic_publish(
+                exchange,
+                routing_key,
+                BasicPublishOptions::default(),
+                routing_key.as_bytes().to_vec(),
+                BasicProperties::default().with_headers(headers.clone()),
+            )
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+    }
+
+    // Only the bound routing keys and headers reach the queue, once each.
+    let mut received = vec![];
+    while let Some(message) = channel
+        .basic_get("sample_example_bindings", BasicGetOptions { no_ack: true })
+        .await
+        .unwrap()
+    {
+        received.push(String::from_utf8(message.delivery.data).unwrap());
+    }
+    assert_eq!(received, ["user.created", "user.deleted", ""]);
+}
+
 #[tokio::test]
 async fn consumer_changed() {
     let config = create_config();
@@ -148,6 +363,7 @@ async fn consumer_changed() {
         connection,
         queue_config.clone(),
         config.rabbit.queue_prefix.clone(),
+        &config.rabbit.exchanges,
     )
     .await
     .unwrap();
@@ -182,3 +398,292 @@ async fn consumer_changed() {
         }
     }
 }
+
+#[tokio::test]
+async fn consumer_handler() {
+    let config = create_config();
+    let data = create_data().await;
+    let mut queue_config: QueueConfig = data.write().await.get_queues().first().unwrap().to_owned();
+    queue_config.queue_name = "example_handler".into();
+
+    let connection = connect(create_config()).await.unwrap();
+
+    let (channel, queue) = Channel::get_queue(
+        connection,
+        queue_config.clone(),
+        config.rabbit.queue_prefix.clone(),
+        &config.rabbit.exchanges,
+    )
+    .await
+    .unwrap();
+
+    assert!(channel
+        .basic_publish(
+            "",
+            &format!("{}{}", config.rabbit.queue_prefix, queue_config.queue_name),
+            BasicPublishOptions::default(),
+            b"This is a test!".to_vec(),
+            BasicProperties::default()
+        )
+        .await
+        .is_ok());
+
+    // The custom handler replaces the command configured for the same queue id.
+    let received = Arc::new(Mutex::new(vec![]));
+    let consumer = Consumer::new(config).add_handler(
+        queue_config.id,
+        Recorder {
+            received: received.clone(),
+        },
+    );
+
+    let _ = tokio::time::timeout(
+        Duration::from_secs(2),
+        consumer.consume(0, queue_config, channel, queue),
+    )
+    .await;
+
+    assert_eq!(*received.lock().await, vec![b"This is a test!".to_vec()]);
+}
+
+#[tokio::test]
+async fn consumer_batch() {
+    let path = std::env::temp_dir().join("rabbitmq_consumer_batch");
+    let _ = fs::remove_file(&path);
+
+    let channel = consume_bodies(
+        |queue_config| {
+            queue_config.queue_name = "example_batch".into();
+            queue_config.command = format!("cat >> {}", path.display());
+            queue_config.shell = Some(true);
+            queue_config.batch_size = Some(3);
+        },
+        &["1", "2", "3"],
+        3,
+    )
+    .await;
+
+    // The bodies reach the command in order, and the whole batch is acknowledged.
+    assert_eq!(fs::read_to_string(&path).unwrap(), "1\n2\n3\n");
+    assert!(channel
+        .basic_get("sample_example_batch", BasicGetOptions::default())
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn consumer_batch_dead_letter() {
+    let connection = connect(create_config()).await.unwrap();
+    let channel = connection.create_channel().await.unwrap();
+    channel
+        .queue_declare(
+            "sample_example_batch_failed",
+            QueueDeclareOptions::default(),
+            FieldTable::default(),
+        )
+        .await
+        .unwrap();
+    channel
+        .queue_purge("sample_example_batch_failed", Default::default())
+        .await
+        .unwrap();
+
+    let channel = consume_bodies(
+        |queue_config| {
+            queue_config.queue_name = "example_batch_dead_letter".into();
+            queue_config.command = "exit 2".into();
+            queue_config.shell = Some(true);
+            queue_config.batch_size = Some(2);
+            queue_config.dead_letter_queue = Some("sample_example_batch_failed".into());
+        },
+        &["1", "2"],
+        3,
+    )
+    .await;
+
+    // Each message of a failed batch is dead-lettered on its own with the report headers.
+    for body in &["1", "2"] {
+        let message = channel
+            .basic_get(
+                "sample_example_batch_failed",
+                BasicGetOptions { no_ack: true },
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let headers = message.delivery.properties.headers().clone().unwrap();
+
+        assert_eq!(message.delivery.data, body.as_bytes());
+        assert_eq!(
+            headers.inner().get("x-consumer-exit-code"),
+            Some(&AMQPValue::LongInt(2))
+        );
+        assert_eq!(
+            headers.inner().get("x-consumer-attempts"),
+            Some(&AMQPValue::LongLongInt(1))
+        );
+    }
+    assert!(channel
+        .basic_get(
+            "sample_example_batch_dead_letter",
+            BasicGetOptions::default()
+        )
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn consumer_dead_letter_unroutable() {
+    let path = std::env::temp_dir().join("rabbitmq_consumer_dead_letter_unroutable");
+    let _ = fs::remove_file(&path);
+
+    consume_bodies(
+        |queue_config| {
+            queue_config.queue_name = "example_dead_letter_unroutable".into();
+            queue_config.command = format!("echo {{{{body}}}} >> {}; exit 2", path.display());
+            queue_config.shell = Some(true);
+            queue_config.retry_wait = 2;
+            queue_config.dead_letter_queue = Some("sample_example_missing".into());
+        },
+        &["1"],
+        3,
+    )
+    .await;
+
+    // The dead letter can't be routed, so the message is requeued after waiting instead
+    // of running again right away.
+    let lines = fs::read_to_string(&path).unwrap();
+    assert!(matches!(lines.lines().count(), 1 | 2), "{}", lines);
+}
+
+#[tokio::test]
+async fn consumer_delayed_retry() {
+    let path = std::env::temp_dir().join("rabbitmq_consumer_delayed_retry");
+    let _ = fs::remove_file(&path);
+
+    let channel = consume_bodies(
+        |queue_config| {
+            queue_config.queue_name = "example_delayed_retry".into();
+            queue_config.command = format!("echo {{{{body}}}} >> {}; exit 1", path.display());
+            queue_config.shell = Some(true);
+            queue_config.retry_wait = 1;
+            queue_config.max_retries = Some(1);
+            queue_config.delayed_retry = Some(true);
+        },
+        &["1"],
+        4,
+    )
+    .await;
+
+    // The failed message waits in the retry queue named after its TTL, then it's dead
+    // lettered back to the main queue and runs again, the last time allowed.
+    assert_eq!(fs::read_to_string(&path).unwrap(), "1\n1\n");
+    assert!(channel
+        .queue_declare(
+            "sample_example_delayed_retry.retry.1000ms",
+            QueueDeclareOptions {
+                passive: true,
+                ..Default::default()
+            },
+            FieldTable::default(),
+        )
+        .await
+        .is_ok());
+
+    // A different delay declares another retry queue instead of conflicting with it.
+    let config = create_config();
+    let queue_config = QueueConfig {
+        queue_name: "example_delayed_retry".into(),
+        retry_wait: 2,
+        delayed_retry: Some(true),
+        ..config.rabbit.queues[0].clone()
+    };
+    assert!(Channel::get_queue(
+        connect(create_config()).await.unwrap(),
+        queue_config,
+        config.rabbit.queue_prefix.clone(),
+        &config.rabbit.exchanges,
+    )
+    .await
+    .is_ok());
+}
+
+#[tokio::test]
+async fn consumer_reply() {
+    let connection = connect(create_config()).await.unwrap();
+    let channel = connection.create_channel().await.unwrap();
+    channel
+        .queue_declare(
+            "sample_example_replies",
+            QueueDeclareOptions::default(),
+            FieldTable::default(),
+        )
+        .await
+        .unwrap();
+    channel
+        .queue_purge("sample_example_replies", Default::default())
+        .await
+        .unwrap();
+
+    let properties = BasicProperties::default()
+        .with_reply_to("sample_example_replies".into())
+        .with_correlation_id("request-1".into());
+    let channel = consume_messages(
+        |queue_config| {
+            queue_config.queue_name = "example_reply".into();
+            queue_config.command = "printf 0123456789".into();
+            queue_config.reply_mode = Some("raw".into());
+            queue_config.output_limit = Some(4);
+        },
+        &[("", properties)],
+        2,
+    )
+    .await;
+
+    // The reply carries the whole output, whatever the output limit.
+    let reply = channel
+        .basic_get("sample_example_replies", BasicGetOptions { no_ack: true })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(reply.delivery.data, b"0123456789");
+    assert_eq!(
+        reply
+            .delivery
+            .properties
+            .correlation_id()
+            .as_ref()
+            .map(|id| id.as_str()),
+        Some("request-1")
+    );
+}
+
+#[tokio::test]
+async fn consumer_concurrent() {
+    let path = std::env::temp_dir().join("rabbitmq_consumer_concurrent");
+    let _ = fs::remove_file(&path);
+
+    // One message at a time would take three seconds.
+    consume_bodies(
+        |queue_config| {
+            queue_config.queue_name = "example_concurrent".into();
+            queue_config.command = format!("sleep 1; echo {{{{body}}}} >> {}", path.display());
+            queue_config.shell = Some(true);
+            queue_config.concurrency = Some(3);
+        },
+        &["1", "2", "3"],
+        2,
+    )
+    .await;
+
+    let mut lines = fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    lines.sort();
+
+    assert_eq!(lines, vec!["1", "2", "3"]);
+}
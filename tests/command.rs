@@ -0,0 +1,272 @@
+use std::fs;
+
+use tokio::time::{self, Duration, Instant};
+
+use tempfile::tempdir;
+
+use rabbitmq_consumer_lib::client::consumer::message::command::{
+    CommandError, CommandResult, MessageCommand,
+};
+use rabbitmq_consumer_lib::client::consumer::message::payload::Payload;
+use rabbitmq_consumer_lib::client::consumer::output::Capture;
+use rabbitmq_consumer_lib::config::queue::config::QueueConfig;
+use rabbitmq_consumer_lib::config::queue::DeliveryMode;
+
+mod common;
+
+use common::{delivery, queue};
+
+fn build(
+    cmd: &str,
+    shell: bool,
+    delivery_mode: DeliveryMode,
+    body: &str,
+) -> Result<MessageCommand, CommandError> {
+    build_for(
+        &queue(&format!("shell = {}", shell)),
+        cmd,
+        delivery_mode,
+        body,
+    )
+}
+
+fn build_for(
+    queue_config: &QueueConfig,
+    cmd: &str,
+    delivery_mode: DeliveryMode,
+    body: &str,
+) -> Result<MessageCommand, CommandError> {
+    MessageCommand::new(
+        cmd,
+        queue_config,
+        delivery_mode,
+        &Payload::new(body.as_bytes(), None, false),
+        &delivery(body.as_bytes()),
+    )
+}
+
+async fn run(mut message_command: MessageCommand) -> String {
+    match message_command
+        .execute(5000, 1000, &Capture::new("example#0", 1024, None))
+        .await
+    {
+        CommandResult::Output(Ok(output)) => {
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+
+            String::from_utf8(output.stdout).unwrap()
+        }
+        _ => panic!("command not executed"),
+    }
+}
+
+// Waits for the process to exit, a zombie has exited too.
+async fn exited(pid: &str, wait: u64) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(wait) {
+        match fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) if !stat[stat.rfind(')').unwrap()..].starts_with(") Z") => {
+                time::sleep(Duration::from_millis(50)).await
+            }
+            _ => return true,
+        }
+    }
+
+    false
+}
+
+#[tokio::test]
+async fn split() {
+    let message_command = build(
+        r#"printf "%s|" "app:run job" --name='a b'"#,
+        false,
+        DeliveryMode::Argv,
+        r#"--id=1 "two words""#,
+    )
+    .unwrap();
+    assert_eq!(
+        message_command.human,
+        "printf '%s|' 'app:run job' '--name=a b' '--id=1' 'two words'"
+    );
+    assert_eq!(
+        run(message_command).await,
+        "app:run job|--name=a b|--id=1|two words|"
+    );
+
+    // Without a shell the placeholders are single arguments, never interpreted.
+    let message_command = build(
+        "printf %s| {{body.name}} {{routing_key}}",
+        false,
+        DeliveryMode::Argv,
+        r#"{"name":"$(id); exit 3"}"#,
+    )
+    .unwrap();
+    assert_eq!(run(message_command).await, "$(id); exit 3|example|");
+
+    let message_command = build("cat", false, DeliveryMode::Stdin, "body").unwrap();
+    assert_eq!(message_command.human, "cat < (stdin)");
+    assert_eq!(run(message_command).await, "body");
+}
+
+#[tokio::test]
+async fn shell() {
+    let message_command = build("printf '%s|'", true, DeliveryMode::Argv, "a 'b c'").unwrap();
+    assert_eq!(message_command.human, "printf '%s|' a 'b c'");
+    assert_eq!(run(message_command).await, "a|b c|");
+
+    // The placeholder values are quoted, so they reach the command as they are,
+    // whatever they contain.
+    let name = "it's $(id) `id`; \"exit\" 3 \\ \n";
+    let message_command = build(
+        "printf %s {{body.name}}",
+        true,
+        DeliveryMode::Argv,
+        &serde_json::json!({ "name": name }).to_string(),
+    )
+    .unwrap();
+    assert_eq!(
+        message_command.human,
+        format!("printf %s {}", shell_words::quote(name))
+    );
+    assert_eq!(run(message_command).await, name);
+}
+
+#[tokio::test]
+async fn timeout() {
+    let capture = Capture::new("example#0", 1024, None);
+
+    // The command exits on SIGTERM within the grace period.
+    let mut message_command = build(
+        "trap 'exit 0' TERM; while true; do sleep 0.1; done",
+        true,
+        DeliveryMode::Stdin,
+        "body",
+    )
+    .unwrap();
+    assert!(matches!(
+        message_command.execute(500, 5000, &capture).await,
+        CommandResult::Timeout(false)
+    ));
+
+    // The command ignores SIGTERM, so it is killed once the grace period is over.
+    let mut message_command = build(
+        "trap '' TERM; while true; do sleep 0.1; done",
+        true,
+        DeliveryMode::Stdin,
+        "body",
+    )
+    .unwrap();
+    assert!(matches!(
+        message_command.execute(500, 300, &capture).await,
+        CommandResult::Timeout(true)
+    ));
+}
+
+#[tokio::test]
+async fn dropped() {
+    let dir = tempdir().unwrap();
+    let capture = Capture::new("example#0", 1024, None);
+
+    // The command exits on SIGTERM, or is killed once the grace period is over.
+    for (trap, kill_grace) in &[("'exit 0'", 60000), ("''", 300)] {
+        let pid_file = dir.path().join("pid");
+        let mut message_command = build(
+            &format!(
+                "echo $$ > {}; trap {} TERM; while true; do sleep 0.1; done",
+                pid_file.display(),
+                trap
+            ),
+            true,
+            DeliveryMode::Stdin,
+            "body",
+        )
+        .unwrap();
+
+        // The execution is given up before the command times out.
+        assert!(time::timeout(
+            Duration::from_millis(500),
+            message_command.execute(60000, *kill_grace, &capture),
+        )
+        .await
+        .is_err());
+
+        let pid = fs::read_to_string(&pid_file).unwrap();
+        assert!(exited(pid.trim(), 3000).await, "{} still running", pid);
+    }
+}
+
+#[tokio::test]
+async fn environment() {
+    let dir = tempdir().unwrap();
+    let queue_config = queue(&format!(
+        r#"
+            shell = true
+            working_dir = "{}"
+            env_clear = true
+            env = {{ PATH = "/usr/bin:/bin", APP_ENV = "test" }}
+            rlimit_cpu = 30
+            rlimit_as = 1073741824
+            rlimit_nofile = 64
+        "#,
+        dir.path().display()
+    ));
+
+    let message_command = build_for(
+        &queue_config,
+        r#"pwd; echo "$APP_ENV|$HOME|$PATH"; ulimit -t; ulimit -v; ulimit -n"#,
+        DeliveryMode::Stdin,
+        "body",
+    )
+    .unwrap();
+    assert_eq!(
+        run(message_command).await,
+        format!(
+            "{}\ntest||/usr/bin:/bin\n30\n1048576\n64\n",
+            dir.path().display()
+        )
+    );
+}
+
+#[tokio::test]
+async fn user() {
+    // Only root can run the commands as another user.
+    if unsafe { libc::geteuid() } != 0 {
+        return;
+    }
+
+    // The body file is readable by the user running the command.
+    let queue_config = queue("uid = 65534\ngid = 65534");
+    let message_command = build_for(
+        &queue_config,
+        r#"sh -c 'id -u; id -g; cat "$2"' sh"#,
+        DeliveryMode::Tempfile,
+        "body",
+    )
+    .unwrap();
+    assert_eq!(run(message_command).await, "65534\n65534\nbody");
+}
+
+#[test]
+fn errors() {
+    for cmd in &["", "   "] {
+        assert!(matches!(
+            build(cmd, false, DeliveryMode::Stdin, "body"),
+            Err(CommandError::InvalidCommand(_))
+        ));
+    }
+    assert!(matches!(
+        build("echo \"open", false, DeliveryMode::Stdin, "body"),
+        Err(CommandError::InvalidCommand(_))
+    ));
+    assert!(matches!(
+        build("echo", false, DeliveryMode::Argv, "\"open"),
+        Err(CommandError::InvalidBody(_))
+    ));
+    assert!(matches!(
+        build("echo {{headers.x-missing}}", true, DeliveryMode::Stdin, "body"),
+        Err(CommandError::MissingPlaceholder(key)) if key == "headers.x-missing"
+    ));
+}
@@ -0,0 +1,56 @@
+// Fixtures shared by the integration tests, each test file only uses some of them.
+#![allow(dead_code)]
+
+use lapin::message::Delivery;
+use lapin::types::{AMQPValue, FieldTable, ShortString};
+use lapin::BasicProperties;
+
+use rabbitmq_consumer_lib::config::queue::config::QueueConfig;
+
+// Parses the "example" queue running "echo", with the extra TOML lines appended.
+pub fn parse_queue(extra: &str) -> Result<QueueConfig, toml::de::Error> {
+    toml::from_str::<QueueConfig>(&format!(
+        r#"
+            id = 1
+            queue_name = "example"
+            consumer_name = "example"
+            command = "echo"
+            base64 = false
+            count = 1
+            retry_wait = 10
+            retry_mode = "static"
+            enabled = true
+            {}
+        "#,
+        extra
+    ))
+}
+
+pub fn queue(extra: &str) -> QueueConfig {
+    parse_queue(extra).unwrap()
+}
+
+pub fn delivery(data: &[u8]) -> Delivery {
+    delivery_with(data, BasicProperties::default())
+}
+
+pub fn delivery_with(data: &[u8], properties: BasicProperties) -> Delivery {
+    Delivery {
+        delivery_tag: 1,
+        exchange: "".into(),
+        routing_key: "example".into(),
+        redelivered: false,
+        properties,
+        data: data.to_vec(),
+        acker: Default::default(),
+    }
+}
+
+pub fn headers(headers: Vec<(&str, AMQPValue)>) -> FieldTable {
+    let mut table = FieldTable::default();
+    for (name, value) in headers {
+        table.insert(ShortString::from(name), value);
+    }
+
+    table
+}
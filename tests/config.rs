@@ -1,12 +1,22 @@
+use std::os::unix::process::ExitStatusExt;
 use std::panic;
+use std::process::ExitStatus;
 
 use async_std::net::ToSocketAddrs;
 use async_std::sync::{Arc, RwLock};
 
+use rabbitmq_consumer_lib::client::consumer::expiry;
+use rabbitmq_consumer_lib::config::queue::binding::{Argument, Bindings};
+use rabbitmq_consumer_lib::config::queue::environment::Environment;
+use rabbitmq_consumer_lib::config::queue::outcome::{Outcome, Outcomes};
 use rabbitmq_consumer_lib::config::queue::{
-    self, config::QueueConfig, Queue, RetryMode, RetryType,
+    self, config::QueueConfig, Charset, Queue, RetryMode, RetryType,
 };
-use rabbitmq_consumer_lib::config::{file::File, Config};
+use rabbitmq_consumer_lib::config::{file::File, Config, RabbitConfig};
+
+mod common;
+
+use common::{parse_queue, queue};
 
 #[test]
 fn file_read_dev() {
@@ -113,6 +123,44 @@ async fn retry_type() {
             retry_wait: 120,
             retry_mode: "static".into(),
             enabled: true,
+            delivery_mode: None,
+            env_headers: None,
+            shell: None,
+            kill_grace: None,
+            outcomes: None,
+            dead_letter_exchange: None,
+            dead_letter_queue: None,
+            max_retries: None,
+            delayed_retry: None,
+            reply_mode: None,
+            results_exchange: None,
+            handler: None,
+            url: None,
+            script_filename: None,
+            batch_size: None,
+            batch_timeout: None,
+            concurrency: None,
+            working_dir: None,
+            env: None,
+            env_clear: None,
+            uid: None,
+            gid: None,
+            rlimit_cpu: None,
+            rlimit_as: None,
+            rlimit_nofile: None,
+            charset: None,
+            max_decompressed_size: None,
+            dedup_window: None,
+            dedup_key: None,
+            dedup_path: None,
+            max_age: None,
+            age_header: None,
+            stale_outcome: None,
+            schema: None,
+            output_limit: None,
+            output_log: None,
+            output_log_size: None,
+            bindings: None,
         },
         QueueConfig {
             id: 2,
@@ -129,6 +177,44 @@ async fn retry_type() {
             retry_wait: 120,
             retry_mode: "ignored".into(),
             enabled: false,
+            delivery_mode: None,
+            env_headers: None,
+            shell: None,
+            kill_grace: None,
+            outcomes: None,
+            dead_letter_exchange: None,
+            dead_letter_queue: None,
+            max_retries: None,
+            delayed_retry: None,
+            reply_mode: None,
+            results_exchange: None,
+            handler: None,
+            url: None,
+            script_filename: None,
+            batch_size: None,
+            batch_timeout: None,
+            concurrency: None,
+            working_dir: None,
+            env: None,
+            env_clear: None,
+            uid: None,
+            gid: None,
+            rlimit_cpu: None,
+            rlimit_as: None,
+            rlimit_nofile: None,
+            charset: None,
+            max_decompressed_size: None,
+            dedup_window: None,
+            dedup_key: None,
+            dedup_path: None,
+            max_age: None,
+            age_header: None,
+            stale_outcome: None,
+            schema: None,
+            output_limit: None,
+            output_log: None,
+            output_log_size: None,
+            bindings: None,
         },
         QueueConfig {
             id: 3,
@@ -145,6 +231,44 @@ async fn retry_type() {
             retry_wait: 120,
             retry_mode: "incremental".into(),
             enabled: true,
+            delivery_mode: None,
+            env_headers: None,
+            shell: None,
+            kill_grace: None,
+            outcomes: None,
+            dead_letter_exchange: None,
+            dead_letter_queue: None,
+            max_retries: None,
+            delayed_retry: None,
+            reply_mode: None,
+            results_exchange: None,
+            handler: None,
+            url: None,
+            script_filename: None,
+            batch_size: None,
+            batch_timeout: None,
+            concurrency: None,
+            working_dir: None,
+            env: None,
+            env_clear: None,
+            uid: None,
+            gid: None,
+            rlimit_cpu: None,
+            rlimit_as: None,
+            rlimit_nofile: None,
+            charset: None,
+            max_decompressed_size: None,
+            dedup_window: None,
+            dedup_key: None,
+            dedup_path: None,
+            max_age: None,
+            age_header: None,
+            stale_outcome: None,
+            schema: None,
+            output_limit: None,
+            output_log: None,
+            output_log_size: None,
+            bindings: None,
         },
     ];
 
@@ -165,3 +289,315 @@ async fn retry_type() {
         }
     }
 }
+
+#[test]
+fn outcomes() {
+    let outcomes: Outcomes = "0=ack, 1=retry, 3-9=dead-letter, 4=requeue, SIGKILL=requeue, timeout=reject, error=requeue"
+        .parse()
+        .unwrap();
+
+    assert_eq!(outcomes.exit(&ExitStatus::from_raw(0), None), Outcome::Ack);
+    assert_eq!(
+        outcomes.exit(&ExitStatus::from_raw(1 << 8), None),
+        Outcome::Retry
+    );
+    assert_eq!(
+        outcomes.exit(&ExitStatus::from_raw(3 << 8), None),
+        Outcome::DeadLetter
+    );
+    assert_eq!(
+        outcomes.exit(&ExitStatus::from_raw(4 << 8), None),
+        Outcome::Requeue
+    );
+    assert_eq!(
+        outcomes.exit(&ExitStatus::from_raw(9 << 8), None),
+        Outcome::DeadLetter
+    );
+    assert_eq!(
+        outcomes.exit(&ExitStatus::from_raw(10 << 8), None),
+        Outcome::DeadLetter
+    );
+    assert_eq!(
+        outcomes.exit(&ExitStatus::from_raw(libc::SIGKILL), None),
+        Outcome::Requeue
+    );
+    assert_eq!(outcomes.timeout(), Outcome::Reject);
+    assert_eq!(outcomes.error(), Outcome::Requeue);
+
+    let defaults = Outcomes::default();
+
+    assert_eq!(defaults.exit(&ExitStatus::from_raw(0), None), Outcome::Ack);
+    assert_eq!(
+        defaults.exit(&ExitStatus::from_raw(1 << 8), None),
+        Outcome::Retry
+    );
+    assert_eq!(
+        defaults.exit(&ExitStatus::from_raw(2 << 8), None),
+        Outcome::DeadLetter
+    );
+    assert_eq!(
+        defaults.exit(&ExitStatus::from_raw(5 << 8), Some(5)),
+        Outcome::DeadLetter
+    );
+    assert_eq!(
+        defaults.exit(&ExitStatus::from_raw(2 << 8), Some(5)),
+        Outcome::Retry
+    );
+    assert_eq!(
+        defaults.exit(&ExitStatus::from_raw(libc::SIGTERM), None),
+        Outcome::DeadLetter
+    );
+    assert_eq!(defaults.timeout(), Outcome::Requeue);
+    assert_eq!(defaults.error(), Outcome::DeadLetter);
+
+    assert_eq!(defaults.status(204), Outcome::Ack);
+    assert_eq!(defaults.status(429), Outcome::Retry);
+    assert_eq!(defaults.status(502), Outcome::Retry);
+    assert_eq!(defaults.status(404), Outcome::DeadLetter);
+    assert_eq!(
+        "404=ack, 500-503=reject"
+            .parse::<Outcomes>()
+            .unwrap()
+            .status(404),
+        Outcome::Ack
+    );
+
+    assert!("1=unknown".parse::<Outcomes>().is_err());
+    assert!("a-b=ack".parse::<Outcomes>().is_err());
+    assert!("SIGFOO=ack".parse::<Outcomes>().is_err());
+}
+
+#[test]
+fn retry_delays() {
+    let delayed = |retry_mode: &str, max_retries: &str| {
+        let mut queue = queue(&format!("delayed_retry = true\n{}", max_retries));
+        queue.retry_mode = retry_mode.into();
+
+        queue
+    };
+
+    assert!(delayed("static", "").has_delayed_retry());
+    assert_eq!(
+        delayed("static", "max_retries = 3").retry_delays(),
+        vec![10000]
+    );
+    assert_eq!(
+        delayed("incremental", "max_retries = 3").retry_delays(),
+        vec![10000, 20000, 40000]
+    );
+    assert_eq!(
+        delayed("incremental", "").retry_delays().len(),
+        queue::DEFAULT_RETRY_TIERS as usize
+    );
+    assert_eq!(
+        delayed("incremental", "max_retries = 100")
+            .retry_delays()
+            .len(),
+        queue::MAX_RETRY_TIERS as usize
+    );
+    assert_eq!(
+        queue::retry_queue_name("queue_example", 20000),
+        "queue_example.retry.20000ms"
+    );
+}
+
+#[test]
+fn batch() {
+    assert!(!queue("").has_batch());
+    assert_eq!(queue("").batch_limit(), 1);
+    assert_eq!(queue("batch_size = 0").batch_limit(), 1);
+    assert_eq!(queue("").batch_wait(), queue::DEFAULT_BATCH_TIMEOUT);
+
+    let batch = queue("batch_size = \"50\"\nbatch_timeout = 500");
+    assert!(batch.has_batch());
+    assert_eq!(batch.batch_limit(), 50);
+    assert_eq!(batch.batch_wait(), 500);
+}
+
+#[test]
+fn concurrency() {
+    assert_eq!(queue("").concurrency_limit(), 1);
+    assert_eq!(queue("concurrency = -1").concurrency_limit(), 1);
+    assert_eq!(queue("concurrency = \"8\"").concurrency_limit(), 8);
+}
+
+#[test]
+fn environment() {
+    let environment: Environment = "APP_ENV=prod, TEAM=search,EMPTY=".parse().unwrap();
+
+    assert_eq!(
+        environment.variables(),
+        &[
+            ("APP_ENV".to_string(), "prod".to_string()),
+            ("TEAM".to_string(), "search".to_string()),
+            ("EMPTY".to_string(), String::new()),
+        ]
+    );
+    assert!("APP_ENV".parse::<Environment>().is_err());
+    assert!("=prod".parse::<Environment>().is_err());
+
+    let queue = queue(
+        r#"
+            working_dir = "/tmp"
+            env_clear = true
+            uid = "1000"
+            rlimit_nofile = 64
+            env = { PATH = "/usr/bin:/bin" }
+        "#,
+    );
+
+    assert_eq!(queue.working_dir.as_deref(), Some("/tmp"));
+    assert_eq!(queue.env_clear, Some(true));
+    assert_eq!(queue.uid, Some(1000));
+    assert_eq!(queue.gid, None);
+    assert_eq!(queue.rlimit_nofile, Some(64));
+    assert_eq!(
+        queue.env.unwrap().variables(),
+        &[("PATH".to_string(), "/usr/bin:/bin".to_string())]
+    );
+}
+
+#[test]
+fn charset() {
+    assert_eq!(Charset::Utf8.decode("perché".as_bytes()).unwrap(), "perché");
+    assert!(Charset::Utf8.decode(&[0x70, 0xe9, 0xff]).is_none());
+    assert_eq!(Charset::Latin1.decode(&[0x70, 0xe9, 0xff]).unwrap(), "péÿ");
+    assert_eq!(
+        Charset::Windows1252
+            .decode(&[0x80, 0x20, 0x93, 0x61, 0x94])
+            .unwrap(),
+        "€ \u{201c}a\u{201d}"
+    );
+    assert!(Charset::Windows1252.decode(&[0x61, 0x81]).is_none());
+    assert_eq!(Charset::Ascii.decode(b"plain").unwrap(), "plain");
+    assert!(Charset::Ascii.decode(&[0x70, 0xe9]).is_none());
+
+    for (name, charset) in &[
+        ("utf-8", Charset::Utf8),
+        ("UTF8", Charset::Utf8),
+        ("iso-8859-1", Charset::Latin1),
+        ("latin1", Charset::Latin1),
+        ("Latin-1", Charset::Latin1),
+        ("windows-1252", Charset::Windows1252),
+        ("cp1252", Charset::Windows1252),
+        ("US-ASCII", Charset::Ascii),
+        (" ascii ", Charset::Ascii),
+    ] {
+        assert_eq!(name.parse::<Charset>(), Ok(*charset));
+    }
+    assert_eq!(
+        "utf-16".parse::<Charset>(),
+        Err("unknown charset \"utf-16\"".to_string())
+    );
+
+    // An unknown charset fails the configuration load instead of falling back to UTF-8.
+    assert_eq!(queue("charset = \"latin1\"").charset, Some(Charset::Latin1));
+    assert!(parse_queue("charset = \"ebcdic\"").is_err());
+}
+
+#[test]
+fn stale() {
+    assert_eq!(queue("").max_age, None);
+    assert_eq!(queue("").stale_outcome(), Outcome::Ack);
+    assert_eq!(queue("max_age = \"300\"").max_age, Some(300));
+    assert_eq!(
+        queue("stale_outcome = \"dead-letter\"").stale_outcome(),
+        Outcome::DeadLetter
+    );
+    assert_eq!(
+        queue("stale_outcome = \"reject\"").stale_outcome(),
+        Outcome::Reject
+    );
+
+    // Stale messages can't be retried, and unknown outcomes fail the configuration load.
+    assert!(parse_queue("stale_outcome = \"retry\"").is_err());
+    assert!(parse_queue("stale_outcome = \"requeue\"").is_err());
+    assert!(parse_queue("stale_outcome = \"drop\"").is_err());
+
+    // A database row may still hold them.
+    let mut stale = queue("");
+    stale.stale_outcome = Some(Outcome::Retry);
+    assert_eq!(stale.stale_outcome(), Outcome::Ack);
+
+    assert_eq!(expiry::parse_time("1714557600"), Some(1_714_557_600_000));
+    assert_eq!(expiry::parse_time("1714557600123"), Some(1_714_557_600_123));
+    assert_eq!(
+        expiry::parse_time("2024-05-01T10:00:00Z"),
+        Some(1_714_557_600_000)
+    );
+    assert_eq!(
+        expiry::parse_time("2024-05-01T12:00:00+02:00"),
+        Some(1_714_557_600_000)
+    );
+    assert_eq!(expiry::parse_time("yesterday"), None);
+}
+
+#[test]
+fn bindings() {
+    let queue = queue(
+        r#"
+            bindings = [
+                { exchange = "events", routing_keys = ["user.created", "user.deleted"] },
+                { exchange = "invoices", arguments = { "x-match" = "all", priority = 1 } },
+            ]
+        "#,
+    );
+
+    let bindings = queue.bindings.unwrap();
+    let bindings = bindings.bindings();
+    assert_eq!(bindings.len(), 2);
+    assert_eq!(bindings[0].exchange, "events");
+    assert_eq!(
+        bindings[0].routing_keys(),
+        vec!["user.created", "user.deleted"]
+    );
+    assert_eq!(bindings[1].routing_keys(), vec![""]);
+    assert_eq!(
+        bindings[1].arguments.values().get("x-match"),
+        Some(&Argument::String("all".into()))
+    );
+    assert_eq!(
+        bindings[1].arguments.values().get("priority"),
+        Some(&Argument::Integer(1))
+    );
+
+    let bindings: Bindings =
+        r#"[{"exchange":"events","routing_keys":["user.*"],"arguments":{"x-match":"any"}}]"#
+            .parse()
+            .unwrap();
+    assert_eq!(bindings.bindings()[0].routing_keys(), vec!["user.*"]);
+    assert!("".parse::<Bindings>().unwrap().bindings().is_empty());
+    assert!("events:user.*".parse::<Bindings>().is_err());
+
+    let rabbit = toml::from_str::<RabbitConfig>(
+        r#"
+            host = "127.0.0.1"
+            port = 5672
+            username = "guest"
+            password = "guest"
+            vhost = "/"
+            queue_prefix = "queue_"
+            queues = []
+
+            [[exchanges]]
+            name = "events"
+            type = "topic"
+
+            [[exchanges]]
+            name = "delayed"
+            type = "x-delayed-message"
+            durable = "false"
+            arguments = { "x-delayed-type" = "direct" }
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(rabbit.exchanges.len(), 2);
+    assert_eq!(rabbit.exchanges[0].kind.as_deref(), Some("topic"));
+    assert_eq!(rabbit.exchanges[0].durable, None);
+    assert_eq!(rabbit.exchanges[1].durable, Some(false));
+    assert_eq!(
+        rabbit.exchanges[1].arguments.values().get("x-delayed-type"),
+        Some(&Argument::String("direct".into()))
+    );
+}
@@ -0,0 +1,65 @@
+use std::io::Write;
+
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+
+use rabbitmq_consumer_lib::client::consumer::decompress::{self, DecompressError};
+
+const BODY: &[u8] = br#"{"id":1,"items":["a","b","c"]}"#;
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(data).unwrap();
+
+    encoder.finish().unwrap()
+}
+
+#[test]
+fn encodings() {
+    assert!(decompress::is_compressed("gzip"));
+    assert!(decompress::is_compressed(" Deflate"));
+    assert!(decompress::is_compressed("zstd"));
+    assert!(decompress::is_compressed("br"));
+    assert!(!decompress::is_compressed("identity"));
+    assert!(!decompress::is_compressed("utf-8"));
+
+    assert_eq!(
+        decompress::decompress("gzip", &gzip(BODY), 1024).unwrap(),
+        BODY
+    );
+
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(BODY).unwrap();
+    assert_eq!(
+        decompress::decompress("deflate", &encoder.finish().unwrap(), 1024).unwrap(),
+        BODY
+    );
+
+    assert_eq!(
+        decompress::decompress("zstd", &zstd::encode_all(BODY, 3).unwrap(), 1024).unwrap(),
+        BODY
+    );
+
+    assert_eq!(decompress::decompress("utf-8", BODY, 1024).unwrap(), BODY);
+}
+
+#[test]
+fn invalid() {
+    match decompress::decompress("gzip", BODY, 1024) {
+        Err(DecompressError::IoError(_)) => {}
+        result => panic!("{:?}", result),
+    }
+
+    let bomb = gzip(&vec![0; 1024 * 1024]);
+    assert!(bomb.len() < 4096);
+    match decompress::decompress("gzip", &bomb, 1024 * 1024 - 1) {
+        Err(DecompressError::TooLarge(limit)) => assert_eq!(limit, 1024 * 1024 - 1),
+        result => panic!("{:?}", result.map(|data| data.len())),
+    }
+    assert_eq!(
+        decompress::decompress("gzip", &bomb, 1024 * 1024)
+            .unwrap()
+            .len(),
+        1024 * 1024
+    );
+}
@@ -0,0 +1,121 @@
+use std::io::Write;
+
+use tempfile::tempdir;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use lapin::BasicProperties;
+
+use rabbitmq_consumer_lib::client::consumer::dedup::{self, DedupStore};
+
+mod common;
+
+use common::{delivery, delivery_with};
+
+const WINDOW: u64 = 60;
+const LIMIT: usize = 1024 * 1024;
+
+#[test]
+fn window() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("example.dedup");
+
+    let store = DedupStore::open(&path, WINDOW, 1000).unwrap();
+    assert!(store.is_empty().unwrap());
+    assert!(!store.contains("id:1", 1000).unwrap());
+
+    store.insert("id:1", 1000).unwrap();
+    assert!(store.contains("id:1", 1000).unwrap());
+    assert!(store.contains("id:1", 1059).unwrap());
+    assert!(!store.contains("id:1", 1060).unwrap());
+    assert!(!store.contains("id:2", 1000).unwrap());
+}
+
+#[test]
+fn persistence() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("example.dedup");
+
+    let store = DedupStore::open(&path, WINDOW, 1000).unwrap();
+    store.insert("id:1", 1000).unwrap();
+    store.insert("id:2", 1030).unwrap();
+    store.insert("id:with\nnewline", 1030).unwrap();
+    drop(store);
+
+    // The expired keys are dropped when the store is opened again.
+    let store = DedupStore::open(&path, WINDOW, 1070).unwrap();
+    assert_eq!(store.len().unwrap(), 2);
+    assert!(!store.contains("id:1", 1070).unwrap());
+    assert!(store.contains("id:2", 1070).unwrap());
+    assert!(store.contains("id:with\nnewline", 1070).unwrap());
+}
+
+#[test]
+fn shared() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("example.dedup");
+
+    // The stores of two consumer processes on the same file see each other's keys, and
+    // the expiration by one of them doesn't drop the keys inserted by the other.
+    let first = DedupStore::open(&path, WINDOW, 1000).unwrap();
+    let second = DedupStore::open(&path, WINDOW, 1000).unwrap();
+    first.insert("id:1", 1000).unwrap();
+    second.insert("id:2", 1050).unwrap();
+    assert!(second.contains("id:1", 1000).unwrap());
+    assert!(first.contains("id:2", 1050).unwrap());
+
+    let third = DedupStore::open(&path, WINDOW, 1070).unwrap();
+    assert!(first.contains("id:2", 1070).unwrap());
+    assert_eq!(third.len().unwrap(), 1);
+}
+
+#[test]
+fn compaction() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("example.dedup");
+
+    let store = DedupStore::open(&path, WINDOW, 0).unwrap();
+    for now in 0..3000 {
+        store.insert(format!("id:{}", now), now).unwrap();
+    }
+
+    // The expired keys are dropped every thousand insertions.
+    assert!(store.len().unwrap() <= WINDOW as usize + 1001);
+    assert!(store.contains("id:2999", 2999).unwrap());
+}
+
+#[test]
+fn key() {
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(b"{\"id\":1}").unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    let plain = delivery(b"{\"id\":1}");
+    let gzip = delivery_with(
+        &compressed,
+        BasicProperties::default().with_content_encoding("gzip".into()),
+    );
+    let other = delivery(b"{\"id\":2}");
+
+    // The decompressed body is hashed, so the same message has the same key either way.
+    assert!(dedup::key(&plain, false, LIMIT).starts_with("hash:"));
+    assert_eq!(
+        dedup::key(&plain, false, LIMIT),
+        dedup::key(&gzip, false, LIMIT)
+    );
+    assert_ne!(
+        dedup::key(&plain, false, LIMIT),
+        dedup::key(&other, false, LIMIT)
+    );
+
+    let identified = delivery_with(
+        b"{\"id\":1}",
+        BasicProperties::default().with_message_id("message-1".into()),
+    );
+    assert_eq!(dedup::key(&identified, false, LIMIT), "id:message-1");
+    assert_eq!(
+        dedup::key(&identified, true, LIMIT),
+        dedup::key(&plain, true, LIMIT)
+    );
+}
@@ -0,0 +1,255 @@
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, UnixListener};
+
+use async_std::sync::{Arc, RwLock};
+
+use rabbitmq_consumer_lib::client::consumer::fastcgi::{self, FastCgiError};
+use rabbitmq_consumer_lib::client::consumer::handler::{
+    FastCgiHandler, Handled, HandlerContext, MessageHandler,
+};
+use rabbitmq_consumer_lib::config::file::File;
+use rabbitmq_consumer_lib::config::queue::config::QueueConfig;
+use rabbitmq_consumer_lib::config::queue::outcome::Outcome;
+use rabbitmq_consumer_lib::config::queue::Queue;
+
+mod common;
+
+use common::{delivery, queue};
+
+const LIMIT: usize = 1024;
+
+fn record(kind: u8, content: &[u8]) -> Vec<u8> {
+    let mut record = vec![1, kind, 0, 1];
+    record.extend_from_slice(&(content.len() as u16).to_be_bytes());
+    record.extend_from_slice(&[0, 0]);
+    record.extend_from_slice(content);
+
+    record
+}
+
+async fn serve<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, stdout: &[u8]) -> Vec<u8> {
+    // Reads the records until the empty STDIN record closing the request.
+    let mut request = vec![];
+    let mut stdin = vec![];
+    loop {
+        let mut header = [0u8; 8];
+        stream.read_exact(&mut header).await.unwrap();
+        let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+        let mut content = vec![0u8; length + header[6] as usize];
+        stream.read_exact(&mut content).await.unwrap();
+        content.truncate(length);
+
+        request.extend_from_slice(&content);
+        if header[1] == 5 {
+            if length == 0 {
+                break;
+            }
+            stdin.extend_from_slice(&content);
+        }
+    }
+
+    let mut response = record(6, stdout);
+    response.extend(record(7, b"a warning"));
+    response.extend(record(3, &[0; 8]));
+    stream.write_all(&response).await.unwrap();
+
+    request.extend_from_slice(b"|stdin:");
+    request.extend(stdin);
+
+    request
+}
+
+#[tokio::test]
+async fn tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("tcp://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+
+        serve(
+            stream,
+            b"Status: 404 Not Found\r\nX-Consumer-Outcome: ack\r\nContent-Type: text/plain\r\n\r\nnot found",
+        )
+        .await
+    });
+
+    let response = fastcgi::request(
+        address.as_str(),
+        "/var/www/consume.php",
+        &[("AMQP_ROUTING_KEY".into(), "example".into())],
+        b"body",
+        LIMIT,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(response.status, 404);
+    assert_eq!(response.header("x-consumer-outcome"), Some("ack"));
+    assert_eq!(response.body, b"not found");
+    assert_eq!(response.stderr, b"a warning");
+
+    let request = String::from_utf8_lossy(&server.await.unwrap()).to_string();
+
+    assert!(request.contains("SCRIPT_FILENAME/var/www/consume.php"));
+    assert!(request.contains("REQUEST_METHODPOST"));
+    assert!(request.contains("CONTENT_LENGTH4"));
+    assert!(request.contains("AMQP_ROUTING_KEYexample"));
+    assert!(request.ends_with("|stdin:body"));
+}
+
+#[tokio::test]
+async fn unix() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join("php-fpm.sock");
+    let listener = UnixListener::bind(&path).unwrap();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+
+        serve(stream, b"Content-Type: text/plain\r\n\r\ndone").await
+    });
+
+    let address = format!("unix:{}", path.to_string_lossy());
+    let body = vec![b'a'; 70000];
+    let response = fastcgi::request(address.as_str(), "/var/www/consume.php", &[], &body, LIMIT)
+        .await
+        .unwrap();
+
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, b"done");
+}
+
+#[tokio::test]
+async fn invalid() {
+    assert!(
+        fastcgi::request("tcp://", "/var/www/consume.php", &[], b"body", LIMIT)
+            .await
+            .is_err()
+    );
+    assert!(fastcgi::request(
+        "unix:/not/existing.sock",
+        "/var/www/consume.php",
+        &[],
+        b"body",
+        LIMIT
+    )
+    .await
+    .is_err());
+}
+
+async fn listen(stdout: &'static [u8]) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("tcp://{}", listener.local_addr().unwrap());
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+
+        serve(stream, stdout).await
+    });
+
+    address
+}
+
+#[tokio::test]
+async fn limit() {
+    // The headers of the three records, their contents and the 8 bytes closing the request.
+    let stdout = b"Content-Type: text/plain\r\n\r\ndone";
+    let length = 3 * 8 + stdout.len() + b"a warning".len() + 8;
+
+    let address = listen(stdout).await;
+    assert!(fastcgi::request(
+        address.as_str(),
+        "/var/www/consume.php",
+        &[],
+        b"body",
+        length
+    )
+    .await
+    .is_ok());
+
+    // A larger response isn't read any further.
+    let address = listen(stdout).await;
+    assert!(matches!(
+        fastcgi::request(
+            address.as_str(),
+            "/var/www/consume.php",
+            &[],
+            b"body",
+            length - 1
+        )
+        .await,
+        Err(FastCgiError::InvalidResponse(_))
+    ));
+}
+
+async fn respond(stdout: &'static [u8], queue_config: QueueConfig) -> Handled {
+    let queue_config = QueueConfig {
+        url: Some(listen(stdout).await),
+        script_filename: Some("/var/www/consume.php".into()),
+        ..queue_config
+    };
+    let handler = FastCgiHandler::new(Arc::new(RwLock::new(Queue::new(Box::new(File::new(
+        vec![queue_config.clone()],
+    ))))));
+
+    let delivery = delivery(b"body");
+    let context = HandlerContext {
+        index: 0,
+        queue_name: "sample_example".into(),
+        queue_config: &queue_config,
+        delivery: &delivery,
+    };
+
+    handler.handle(&context).await.unwrap()
+}
+
+#[tokio::test]
+async fn handler() {
+    let handled = respond(b"Content-Type: text/plain\r\n\r\ndone", queue("")).await;
+    assert_eq!(handled.outcome, Outcome::Ack);
+    assert_eq!(handled.report.stdout, b"done");
+    assert_eq!(handled.report.stderr, b"a warning");
+
+    // The status is mapped as in the HTTP handler.
+    let outcome = |stdout| async move { respond(stdout, queue("")).await.outcome };
+    assert_eq!(outcome(b"Status: 201 Created\r\n\r\n").await, Outcome::Ack);
+    assert_eq!(
+        outcome(b"Status: 429 Too Many\r\n\r\n").await,
+        Outcome::Retry
+    );
+    assert_eq!(outcome(b"Status: 500 Error\r\n\r\n").await, Outcome::Retry);
+    assert_eq!(
+        outcome(b"Status: 404 Not Found\r\n\r\n").await,
+        Outcome::DeadLetter
+    );
+
+    // The outcome header wins over the status, unless it's not a known outcome.
+    assert_eq!(
+        outcome(b"Status: 500 Error\r\nX-Consumer-Outcome: reject\r\n\r\n").await,
+        Outcome::Reject
+    );
+    assert_eq!(
+        outcome(b"Status: 404 Not Found\r\nX-Consumer-Outcome: ack\r\n\r\n").await,
+        Outcome::Ack
+    );
+    assert_eq!(
+        outcome(b"Status: 404 Not Found\r\nX-Consumer-Outcome: maybe\r\n\r\n").await,
+        Outcome::DeadLetter
+    );
+
+    // The configured outcomes override the status codes, "ignored" acknowledges everything.
+    let handled = respond(
+        b"Status: 404 Not Found\r\n\r\n",
+        queue(r#"outcomes = { "404" = "requeue" }"#),
+    )
+    .await;
+    assert_eq!(handled.outcome, Outcome::Requeue);
+
+    let ignored = QueueConfig {
+        retry_mode: "ignored".into(),
+        ..queue("")
+    };
+    let handled = respond(b"X-Consumer-Outcome: retry\r\n\r\n", ignored).await;
+    assert_eq!(handled.outcome, Outcome::Ack);
+}
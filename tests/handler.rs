@@ -0,0 +1,312 @@
+use std::io;
+use std::path::Path;
+
+use async_std::sync::{Arc, RwLock};
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+
+use lapin::message::Delivery;
+use lapin::types::AMQPValue;
+use lapin::BasicProperties;
+
+use rabbitmq_consumer_lib::client::consumer::handler::report::{Report, HEADER_OUTPUT_LIMIT};
+use rabbitmq_consumer_lib::client::consumer::handler::{
+    CommandHandler, Handled, HandlerContext, MessageHandler,
+};
+use rabbitmq_consumer_lib::config::file::File;
+use rabbitmq_consumer_lib::config::queue::config::QueueConfig;
+use rabbitmq_consumer_lib::config::queue::outcome::Outcome;
+use rabbitmq_consumer_lib::config::queue::Queue;
+
+use serde_json::Value;
+
+mod common;
+
+use common::{delivery, delivery_with, queue};
+
+struct Echo;
+
+impl Echo {
+    async fn echo(&self, context: &HandlerContext<'_>) -> io::Result<Handled> {
+        let outcome = match context.delivery.data.as_slice() {
+            b"retry" => Outcome::Retry,
+            _ => Outcome::Ack,
+        };
+
+        Ok(Handled::new(
+            outcome,
+            Report::new(
+                format!("Echoed on {}", context.queue_name),
+                context.content_type(),
+                "ok".into(),
+            )
+            .with_stdout(context.body()),
+        ))
+    }
+}
+
+impl MessageHandler for Echo {
+    fn handle<'a>(&'a self, context: &'a HandlerContext<'a>) -> BoxFuture<'a, io::Result<Handled>> {
+        self.echo(context).boxed()
+    }
+}
+
+async fn handle<H: MessageHandler>(
+    handler: &H,
+    queue_config: &QueueConfig,
+    delivery: &Delivery,
+) -> Handled {
+    let context = HandlerContext {
+        index: 0,
+        queue_name: format!("sample_{}", queue_config.queue_name),
+        queue_config,
+        delivery,
+    };
+
+    handler.handle(&context).await.unwrap()
+}
+
+#[tokio::test]
+async fn handle_message() {
+    let text = BasicProperties::default().with_content_type("text/plain".into());
+    let handled = handle(&Echo, &queue(""), &delivery_with(b"hello", text)).await;
+    assert_eq!(handled.outcome, Outcome::Ack);
+    assert_eq!(handled.report.description, "Echoed on sample_example");
+    assert_eq!(handled.report.command, "text/plain");
+    assert_eq!(handled.report.stdout, b"hello");
+
+    let handled = handle(&Echo, &queue(""), &delivery(b"retry")).await;
+    assert_eq!(handled.outcome, Outcome::Retry);
+}
+
+#[tokio::test]
+async fn handle_base64() {
+    let queue_config = QueueConfig {
+        base64: true,
+        ..queue("")
+    };
+    let handled = handle(&Echo, &queue_config, &delivery(b"hello")).await;
+    assert_eq!(handled.outcome, Outcome::Ack);
+    assert_eq!(handled.report.stdout, b"aGVsbG8=");
+}
+
+#[test]
+fn handled() {
+    let handled = Handled::from(Outcome::Requeue);
+    assert_eq!(handled.outcome, Outcome::Requeue);
+    assert_eq!(handled.report.reason, Outcome::Requeue.to_string());
+    assert_eq!(handled.report.exit_code, None);
+}
+
+#[test]
+fn report_limits() {
+    let report = Report::new("Failed", "false", "exit status: 1")
+        .with_stdout(b"0123456789".to_vec())
+        .with_stderr(vec![b'e'; 2 * HEADER_OUTPUT_LIMIT]);
+    let delivery = delivery(b"hello");
+
+    // The headers are capped, whatever the output limit of the queue.
+    let headers = report.headers("sample_example", &delivery, 1, 1024 * 1024);
+    let stderr = match headers.inner().get("x-consumer-stderr") {
+        Some(AMQPValue::LongString(stderr)) => stderr.to_string(),
+        value => panic!("unexpected stderr header {:?}", value),
+    };
+    assert!(stderr.starts_with(&"e".repeat(HEADER_OUTPUT_LIMIT)));
+    assert!(stderr.ends_with(&format!("... ({} bytes truncated)", HEADER_OUTPUT_LIMIT)));
+
+    let headers = report.headers("sample_example", &delivery, 1, 4);
+    assert_eq!(
+        headers.inner().get("x-consumer-stderr"),
+        Some(&AMQPValue::LongString(
+            format!("eeee... ({} bytes truncated)", 2 * HEADER_OUTPUT_LIMIT - 4).into()
+        ))
+    );
+
+    // The events follow the output limit of the queue.
+    let event = report.event("sample_example", 0, &delivery, Outcome::DeadLetter, 4);
+    let event = serde_json::from_slice::<Value>(&event).unwrap();
+    assert_eq!(event["stdout"], "0123... (6 bytes truncated)");
+
+    let event = report.event(
+        "sample_example",
+        0,
+        &delivery,
+        Outcome::DeadLetter,
+        1024 * 1024,
+    );
+    let event = serde_json::from_slice::<Value>(&event).unwrap();
+    assert_eq!(event["stdout"], "0123456789");
+    assert_eq!(
+        event["stderr"].as_str().unwrap().len(),
+        2 * HEADER_OUTPUT_LIMIT
+    );
+}
+
+#[tokio::test]
+async fn command_placeholders() {
+    let queue_config = QueueConfig {
+        command: "printf %s {{body.name}}".into(),
+        ..queue("")
+    };
+    let handler = CommandHandler::new(Arc::new(RwLock::new(Queue::new(Box::new(File::new(
+        vec![queue_config.clone()],
+    ))))));
+
+    let handled = handle(&handler, &queue_config, &delivery(br#"{"name":"example"}"#)).await;
+    assert_eq!(handled.outcome, Outcome::Ack);
+    assert_eq!(handled.report.stdout, b"example");
+
+    // A message without a value for a placeholder can't be executed: it gets the error
+    // outcome, dead-letter by default.
+    let handled = handle(&handler, &queue_config, &delivery(br#"{"other":1}"#)).await;
+    assert_eq!(handled.outcome, Outcome::DeadLetter);
+    assert_eq!(
+        handled.report.reason,
+        "missing value for placeholder \"{{body.name}}\""
+    );
+
+    let queue_config = QueueConfig {
+        command: "printf %s {{body.name}}".into(),
+        ..queue("outcomes = { error = \"reject\" }")
+    };
+    let handled = handle(&handler, &queue_config, &delivery(br#"{"other":1}"#)).await;
+    assert_eq!(handled.outcome, Outcome::Reject);
+}
+
+#[tokio::test]
+async fn command_arguments() {
+    let queue_config = QueueConfig {
+        command: "printf %s|".into(),
+        ..queue("outcomes = { error = \"requeue\" }")
+    };
+    let handler = CommandHandler::new(Arc::new(RwLock::new(Queue::new(Box::new(File::new(
+        vec![queue_config.clone()],
+    ))))));
+
+    let handled = handle(&handler, &queue_config, &delivery(br#"--name "O'Brien""#)).await;
+    assert_eq!(handled.outcome, Outcome::Ack);
+    assert_eq!(handled.report.stdout, b"--name|O'Brien|");
+
+    // An unbalanced quote can't be split into arguments: the configured error outcome
+    // applies.
+    let handled = handle(&handler, &queue_config, &delivery(b"--name O'Brien")).await;
+    assert_eq!(handled.outcome, Outcome::Requeue);
+}
+
+#[tokio::test]
+async fn command_tempfile() {
+    let queue_config = QueueConfig {
+        command: r#"sh -c 'echo "$1"; cat "$2"; echo; echo "$2"' sh"#.into(),
+        delivery_mode: Some("tempfile".into()),
+        ..queue("")
+    };
+    let handler = CommandHandler::new(Arc::new(RwLock::new(Queue::new(Box::new(File::new(
+        vec![queue_config.clone()],
+    ))))));
+
+    // The body is written to a file passed with "--body-file", removed once the command
+    // has exited.
+    let handled = handle(&handler, &queue_config, &delivery(b"hello")).await;
+    assert_eq!(handled.outcome, Outcome::Ack);
+
+    let stdout = String::from_utf8(handled.report.stdout).unwrap();
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines[..2], ["--body-file", "hello"]);
+    assert!(handled
+        .report
+        .command
+        .ends_with(&format!("--body-file {}", lines[2])));
+    assert!(!Path::new(lines[2]).exists());
+}
+
+#[tokio::test]
+async fn command_environment() {
+    let queue_config = QueueConfig {
+        command: "/usr/bin/env".into(),
+        ..queue(
+            r#"
+                delivery_mode = "stdin"
+                env_clear = true
+                env_headers = "x-tenant"
+            "#,
+        )
+    };
+    let handler = CommandHandler::new(Arc::new(RwLock::new(Queue::new(Box::new(File::new(
+        vec![queue_config.clone()],
+    ))))));
+
+    // The message metadata is exported even when the environment is cleared.
+    let properties = BasicProperties::default()
+        .with_message_id("message-1".into())
+        .with_headers(common::headers(vec![
+            ("x-tenant", AMQPValue::LongString("acme".into())),
+            ("x-other", AMQPValue::LongString("other".into())),
+        ]));
+    let handled = handle(
+        &handler,
+        &queue_config,
+        &delivery_with(b"hello", properties),
+    )
+    .await;
+    assert_eq!(handled.outcome, Outcome::Ack);
+
+    let stdout = String::from_utf8(handled.report.stdout).unwrap();
+    let mut variables = stdout.lines().collect::<Vec<_>>();
+    variables.sort_unstable();
+    assert_eq!(
+        variables,
+        [
+            "AMQP_CONSUMER_INDEX=0",
+            "AMQP_DELIVERY_TAG=1",
+            "AMQP_EXCHANGE=",
+            "AMQP_HEADER_X_TENANT=acme",
+            "AMQP_MESSAGE_ID=message-1",
+            "AMQP_QUEUE=sample_example",
+            "AMQP_REDELIVERED=false",
+            "AMQP_ROUTING_KEY=example",
+        ]
+    );
+
+    // A batch has no single message to take the metadata from.
+    let handled = handler
+        .execute_batch(0, &queue_config, &[delivery(b"1"), delivery(b"2")])
+        .await
+        .unwrap();
+    assert_eq!(handled.report.stdout, b"AMQP_BATCH_SIZE=2\n");
+}
+
+#[tokio::test]
+async fn command_reply() {
+    let queue_config = QueueConfig {
+        command: "printf 0123456789".into(),
+        ..queue(
+            r#"
+                output_limit = 4
+                reply_mode = "raw"
+            "#,
+        )
+    };
+    let handler = CommandHandler::new(Arc::new(RwLock::new(Queue::new(Box::new(File::new(
+        vec![queue_config.clone()],
+    ))))));
+
+    // The output published as a reply is never truncated.
+    let reply = BasicProperties::default().with_reply_to("replies".into());
+    let handled = handle(&handler, &queue_config, &delivery_with(b"", reply.clone())).await;
+    assert_eq!(handled.report.stdout, b"0123456789");
+
+    let handled = handler
+        .execute_batch(
+            0,
+            &queue_config,
+            &[delivery(b"1"), delivery_with(b"2", reply)],
+        )
+        .await
+        .unwrap();
+    assert_eq!(handled.report.stdout, b"0123456789");
+
+    // Without a reply only the first bytes are kept.
+    let handled = handle(&handler, &queue_config, &delivery(b"")).await;
+    assert_eq!(handled.report.stdout, b"0123");
+}
@@ -0,0 +1,211 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use async_std::sync::{Arc, RwLock};
+
+use rabbitmq_consumer_lib::client::consumer::handler::{
+    HandlerContext, HttpHandler, MessageHandler,
+};
+use rabbitmq_consumer_lib::client::consumer::http::{self, HttpError};
+use rabbitmq_consumer_lib::config::file::File;
+use rabbitmq_consumer_lib::config::queue::config::QueueConfig;
+use rabbitmq_consumer_lib::config::queue::outcome::Outcome;
+use rabbitmq_consumer_lib::config::queue::Queue;
+
+mod common;
+
+use common::{delivery, queue};
+
+const LIMIT: usize = 1024;
+
+async fn stub(response: &str) -> (String, tokio::task::JoinHandle<String>) {
+    stub_on("127.0.0.1:0", response).await
+}
+
+async fn stub_on(address: &str, response: &str) -> (String, tokio::task::JoinHandle<String>) {
+    let listener = TcpListener::bind(address).await.unwrap();
+    let response = response.to_string();
+    let url = format!(
+        "http://{}/consume?queue=example",
+        listener.local_addr().unwrap()
+    );
+
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        let mut request = vec![];
+        let mut buffer = [0u8; 1024];
+        while !String::from_utf8_lossy(&request).contains("\r\n\r\nbody") {
+            let read = stream.read(&mut buffer).await.unwrap();
+            if read == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..read]);
+        }
+
+        stream.write_all(response.as_bytes()).await.unwrap();
+
+        String::from_utf8_lossy(&request).to_string()
+    });
+
+    (url, server)
+}
+
+#[tokio::test]
+async fn post() {
+    let (url, server) =
+        stub("HTTP/1.1 201 Created\r\nContent-Length: 5\r\nX-Test: yes\r\n\r\nhello").await;
+
+    let response = http::post(
+        &url,
+        &[(
+            "X-AMQP-ROUTING-KEY".into(),
+            "example\r\nX-Injected: 1".into(),
+        )],
+        b"body",
+        LIMIT,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(response.status, 201);
+    assert_eq!(response.reason, "Created");
+    assert_eq!(response.header("x-test"), Some("yes"));
+    assert_eq!(response.body, b"hello");
+
+    let request = server.await.unwrap();
+
+    assert!(request.starts_with("POST /consume?queue=example HTTP/1.1\r\n"));
+    assert!(request.contains("Content-Length: 4\r\n"));
+    assert!(request.contains("X-AMQP-ROUTING-KEY: example  X-Injected: 1\r\n"));
+    assert!(request.ends_with("\r\n\r\nbody"));
+}
+
+#[tokio::test]
+async fn chunked() {
+    let (url, _) = stub(
+        "HTTP/1.1 503 Service Unavailable\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nbusy\r\n7\r\n, later\r\n0\r\n\r\n",
+    )
+    .await;
+
+    let response = http::post(&url, &[], b"body", LIMIT).await.unwrap();
+
+    assert_eq!(response.status, 503);
+    assert_eq!(response.body, b"busy, later");
+}
+
+#[tokio::test]
+async fn ipv6() {
+    let (url, server) = stub_on("[::1]:0", "HTTP/1.1 204 No Content\r\n\r\n").await;
+
+    // The brackets of the IPv6 literal are kept in the Host header only.
+    let response = http::post(&url, &[], b"body", LIMIT).await.unwrap();
+    assert_eq!(response.status, 204);
+    assert!(server.await.unwrap().contains("Host: [::1]:"));
+}
+
+#[tokio::test]
+async fn limit() {
+    let response = "HTTP/1.1 200 OK\r\nContent-Length: 64\r\n\r\n".to_string() + &"a".repeat(64);
+
+    let (url, _) = stub(&response).await;
+    assert!(http::post(&url, &[], b"body", response.len()).await.is_ok());
+
+    // A larger response isn't read any further.
+    let (url, _) = stub(&response).await;
+    assert!(matches!(
+        http::post(&url, &[], b"body", response.len() - 1).await,
+        Err(HttpError::InvalidResponse(_))
+    ));
+}
+
+#[tokio::test]
+async fn invalid() {
+    assert!(matches!(
+        http::post("ftp://localhost/", &[], b"body", LIMIT).await,
+        Err(HttpError::InvalidUrl(_))
+    ));
+    assert!(http::post("not a url", &[], b"body", LIMIT).await.is_err());
+
+    // HTTPS URLs are accepted, the connection itself fails.
+    assert!(matches!(
+        http::post("https://127.0.0.1:1/", &[], b"body", LIMIT).await,
+        Err(HttpError::IoError(_))
+    ));
+
+    let (url, _) = stub("garbage").await;
+
+    assert!(http::post(&url, &[], b"body", LIMIT).await.is_err());
+}
+
+async fn respond(status: &str, queue_config: QueueConfig) -> Outcome {
+    let (url, _) = stub(&format!(
+        "HTTP/1.1 {}\r\nContent-Length: 2\r\n\r\nok",
+        status
+    ))
+    .await;
+    let queue_config = QueueConfig {
+        url: Some(url),
+        ..queue_config
+    };
+    let handler = HttpHandler::new(Arc::new(RwLock::new(Queue::new(Box::new(File::new(
+        vec![queue_config.clone()],
+    ))))));
+
+    let delivery = delivery(b"body");
+    let context = HandlerContext {
+        index: 0,
+        queue_name: "sample_example".into(),
+        queue_config: &queue_config,
+        delivery: &delivery,
+    };
+    let handled = handler.handle(&context).await.unwrap();
+    assert_eq!(handled.report.stdout, b"ok");
+
+    handled.outcome
+}
+
+#[tokio::test]
+async fn handler() {
+    assert_eq!(respond("200 OK", queue("")).await, Outcome::Ack);
+    assert_eq!(respond("204 No Content", queue("")).await, Outcome::Ack);
+    assert_eq!(
+        respond("408 Request Timeout", queue("")).await,
+        Outcome::Retry
+    );
+    assert_eq!(
+        respond("429 Too Many Requests", queue("")).await,
+        Outcome::Retry
+    );
+    assert_eq!(
+        respond("503 Service Unavailable", queue("")).await,
+        Outcome::Retry
+    );
+    assert_eq!(
+        respond("404 Not Found", queue("")).await,
+        Outcome::DeadLetter
+    );
+    assert_eq!(respond("302 Found", queue("")).await, Outcome::DeadLetter);
+
+    // The outcomes override the status codes, and "ignored" acknowledges everything.
+    assert_eq!(
+        respond("404 Not Found", queue(r#"outcomes = { "404" = "ack" }"#)).await,
+        Outcome::Ack
+    );
+    assert_eq!(
+        respond(
+            "500 Internal Server Error",
+            queue(r#"outcomes = { "500-599" = "requeue" }"#)
+        )
+        .await,
+        Outcome::Requeue
+    );
+    let ignored = QueueConfig {
+        retry_mode: "ignored".into(),
+        ..queue("")
+    };
+    assert_eq!(
+        respond("503 Service Unavailable", ignored).await,
+        Outcome::Ack
+    );
+}
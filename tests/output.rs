@@ -0,0 +1,68 @@
+use std::fs;
+
+use tempfile::tempdir;
+
+use rabbitmq_consumer_lib::client::consumer::output::{Capture, OutputLog, Stream};
+
+#[tokio::test]
+async fn drain() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("example.log");
+
+    let capture = Capture::new("example#0", 10, Some(OutputLog::open(&path, 1024).unwrap()));
+    capture.record("command", "echo");
+
+    let retained = capture
+        .drain(
+            &b"first line\r\nsecond line\nno newline"[..],
+            Stream::Stdout,
+        )
+        .await;
+    assert_eq!(retained, b"first line");
+
+    let long = vec![b'a'; 10000];
+    let retained = capture.drain(&long[..], Stream::Stderr).await;
+    assert_eq!(retained.len(), 10);
+
+    let log = fs::read_to_string(&path).unwrap();
+    let records = log.lines().collect::<Vec<&str>>();
+    assert_eq!(records.len(), 6);
+    assert!(records[0].ends_with(" [example#0] command: echo"));
+    assert!(records[1].ends_with(" [example#0] stdout: first line"));
+    assert!(records[3].ends_with(" [example#0] stdout: no newline"));
+    assert!(records[4].ends_with(&format!(" stderr: {}", "a".repeat(8192))));
+    assert!(records[5].ends_with(&format!(" stderr: {}", "a".repeat(10000 - 8192))));
+}
+
+#[test]
+fn rotation() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("example.log");
+    let rotated = |generation: usize| dir.path().join(format!("example.log.{}", generation));
+
+    for execution in 0..8 {
+        let log = OutputLog::open(&path, 10).unwrap();
+        log.write(
+            "example#0",
+            "stdout",
+            format!("execution {}", execution).as_bytes(),
+        )
+        .unwrap();
+    }
+
+    assert!(fs::read_to_string(&path)
+        .unwrap()
+        .ends_with("stdout: execution 7\n"));
+    assert!(fs::read_to_string(rotated(1))
+        .unwrap()
+        .ends_with("stdout: execution 6\n"));
+    assert!(fs::read_to_string(rotated(5))
+        .unwrap()
+        .ends_with("stdout: execution 2\n"));
+    assert!(!rotated(6).exists());
+
+    // Smaller logs are appended to.
+    let log = OutputLog::open(&path, 1024).unwrap();
+    log.write("example#0", "stdout", b"appended\n").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+}
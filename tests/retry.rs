@@ -0,0 +1,124 @@
+use lapin::message::Delivery;
+use lapin::types::{AMQPValue, FieldArray, LongString};
+use lapin::BasicProperties;
+
+use rabbitmq_consumer_lib::client::consumer::message::retry::{self, RETRY_COUNT_HEADER};
+
+mod common;
+
+use common::{delivery, delivery_with};
+
+fn with_headers(headers: Vec<(&str, AMQPValue)>) -> Delivery {
+    delivery_with(
+        b"",
+        BasicProperties::default().with_headers(common::headers(headers)),
+    )
+}
+
+fn death(count: i64) -> AMQPValue {
+    AMQPValue::FieldTable(common::headers(vec![
+        ("queue", AMQPValue::LongString("example".into())),
+        ("count", AMQPValue::LongLongInt(count)),
+    ]))
+}
+
+#[test]
+fn retries() {
+    assert_eq!(retry::retries(&delivery(b"")), 0);
+
+    let cases: Vec<(Vec<(&str, AMQPValue)>, i64)> = vec![
+        (vec![], 0),
+        (vec![("x-other", AMQPValue::LongInt(9))], 0),
+        (vec![(RETRY_COUNT_HEADER, AMQPValue::LongLongInt(2))], 2),
+        (
+            vec![(
+                RETRY_COUNT_HEADER,
+                AMQPValue::LongString(LongString::from("4")),
+            )],
+            4,
+        ),
+        (
+            vec![(
+                RETRY_COUNT_HEADER,
+                AMQPValue::LongString(LongString::from("many")),
+            )],
+            0,
+        ),
+        (vec![("x-delivery-count", AMQPValue::LongInt(3))], 3),
+        (
+            vec![(
+                "x-death",
+                AMQPValue::FieldArray(FieldArray::from(vec![death(1), death(5)])),
+            )],
+            5,
+        ),
+        (
+            vec![(
+                "x-death",
+                AMQPValue::FieldArray(FieldArray::from(vec![AMQPValue::LongInt(7)])),
+            )],
+            0,
+        ),
+        // The highest count wins, whatever header holds it.
+        (
+            vec![
+                (RETRY_COUNT_HEADER, AMQPValue::LongLongInt(2)),
+                ("x-delivery-count", AMQPValue::LongInt(6)),
+                (
+                    "x-death",
+                    AMQPValue::FieldArray(FieldArray::from(vec![death(4)])),
+                ),
+            ],
+            6,
+        ),
+        (
+            vec![
+                (RETRY_COUNT_HEADER, AMQPValue::LongLongInt(8)),
+                ("x-delivery-count", AMQPValue::LongInt(1)),
+                (
+                    "x-death",
+                    AMQPValue::FieldArray(FieldArray::from(vec![death(3)])),
+                ),
+            ],
+            8,
+        ),
+    ];
+
+    for (headers, expected) in cases {
+        let delivery = with_headers(headers);
+        assert_eq!(
+            retry::retries(&delivery),
+            expected,
+            "{:?}",
+            delivery.properties.headers()
+        );
+    }
+}
+
+#[test]
+fn exhausted() {
+    assert!(!retry::exhausted(100, None));
+    assert!(!retry::exhausted(0, Some(3)));
+    assert!(!retry::exhausted(2, Some(3)));
+    assert!(retry::exhausted(3, Some(3)));
+    assert!(retry::exhausted(4, Some(3)));
+    assert!(retry::exhausted(0, Some(0)));
+}
+
+#[test]
+fn headers() {
+    let delivery = with_headers(vec![
+        ("x-tenant", AMQPValue::LongString("acme".into())),
+        (RETRY_COUNT_HEADER, AMQPValue::LongLongInt(1)),
+    ]);
+
+    let headers = retry::headers(&delivery, 2);
+    assert_eq!(
+        headers.inner().get(RETRY_COUNT_HEADER),
+        Some(&AMQPValue::LongLongInt(2))
+    );
+    assert_eq!(
+        headers.inner().get("x-tenant"),
+        Some(&AMQPValue::LongString("acme".into()))
+    );
+}
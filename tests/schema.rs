@@ -0,0 +1,64 @@
+use std::fs;
+
+use serde_json::json;
+use tempfile::tempdir;
+
+use rabbitmq_consumer_lib::client::consumer::schema::{Schema, SchemaError};
+
+fn schema() -> serde_json::Value {
+    json!({
+        "type": "object",
+        "required": ["id", "email"],
+        "properties": {
+            "id": { "type": "integer", "minimum": 1 },
+            "email": { "type": "string" },
+            "tags": { "type": "array", "items": { "type": "string" } }
+        }
+    })
+}
+
+#[test]
+fn validate() {
+    let schema = Schema::new("example.schema.json", &schema()).unwrap();
+
+    assert!(schema
+        .validate(r#"{"id":1,"email":"user@example.com"}"#)
+        .is_ok());
+
+    let errors = schema.validate(r#"{"id":0,"tags":["a",2]}"#).unwrap_err();
+    assert_eq!(errors.len(), 3);
+    assert!(errors.iter().any(|error| error.starts_with("/id: ")));
+    assert!(errors.iter().any(|error| error.starts_with("/tags/1: ")));
+    assert!(errors
+        .iter()
+        .any(|error| error.starts_with("/: ") && error.contains("email")));
+
+    let errors = schema.validate("{\"id\":").unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].starts_with("invalid JSON: "));
+}
+
+#[test]
+fn load() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("example.schema.json");
+
+    assert!(matches!(Schema::load(&path), Err(SchemaError::IoError(_))));
+
+    fs::write(&path, "{").unwrap();
+    assert!(matches!(
+        Schema::load(&path),
+        Err(SchemaError::InvalidJson(_))
+    ));
+
+    fs::write(&path, r#"{"type":"unknown"}"#).unwrap();
+    assert!(matches!(
+        Schema::load(&path),
+        Err(SchemaError::InvalidSchema(_))
+    ));
+
+    fs::write(&path, schema().to_string()).unwrap();
+    let schema = Schema::load(&path).unwrap();
+    assert_eq!(schema.path(), path.as_path());
+    assert!(schema.validate(r#"{"id":2,"email":""}"#).is_ok());
+}
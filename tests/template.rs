@@ -0,0 +1,152 @@
+use lapin::message::Delivery;
+use lapin::types::{AMQPValue, LongString};
+use lapin::BasicProperties;
+
+use rabbitmq_consumer_lib::client::consumer::message::template::Template;
+
+mod common;
+
+use common::delivery_with;
+
+const BODY: &str = r#"{"user":{"name":"O'Brien","tags":["a","b"],"age":42,"none":null}}"#;
+
+fn delivery() -> Delivery {
+    let headers = common::headers(vec![
+        (
+            "X-Tenant",
+            AMQPValue::LongString(LongString::from("acme; rm -rf /")),
+        ),
+        ("x-attempt", AMQPValue::LongInt(3)),
+    ]);
+
+    Delivery {
+        delivery_tag: 7,
+        exchange: "events".into(),
+        routing_key: "user.created".into(),
+        ..delivery_with(
+            BODY.as_bytes(),
+            BasicProperties::default()
+                .with_message_id("message-1".into())
+                .with_headers(headers),
+        )
+    }
+}
+
+#[test]
+fn metadata() {
+    let delivery = delivery();
+    let mut template = Template::new(&delivery, Some(BODY));
+
+    assert_eq!(
+        template
+            .render(
+                "run {{routing_key}} {{ exchange }} {{delivery_tag}} {{message_id}}",
+                false
+            )
+            .unwrap(),
+        "run user.created events 7 message-1"
+    );
+    assert_eq!(
+        template.render("{{body}}", false).unwrap(),
+        BODY.to_string()
+    );
+    assert_eq!(
+        template.render("no placeholders", false).unwrap(),
+        "no placeholders"
+    );
+    assert_eq!(
+        template.render("open {{body", false).unwrap(),
+        "open {{body"
+    );
+}
+
+#[test]
+fn body_path() {
+    let delivery = delivery();
+    let mut template = Template::new(&delivery, Some(BODY));
+
+    assert_eq!(
+        template.render("{{body.user.name}}", false).unwrap(),
+        "O'Brien"
+    );
+    assert_eq!(template.render("{{body.user.tags.1}}", false).unwrap(), "b");
+    assert_eq!(template.render("{{body.user.age}}", false).unwrap(), "42");
+    assert_eq!(
+        template.render("{{body.user.tags}}", false).unwrap(),
+        r#"["a","b"]"#
+    );
+
+    // Null and missing values, or a body that isn't JSON, leave the placeholder
+    // without a value.
+    assert_eq!(
+        template.render("{{body.user.none}}", false),
+        Err("body.user.none".to_string())
+    );
+    assert_eq!(
+        template.render("{{body.user.tags.2}}", false),
+        Err("body.user.tags.2".to_string())
+    );
+    assert_eq!(
+        Template::new(&delivery, Some("plain text")).render("{{body.user}}", false),
+        Err("body.user".to_string())
+    );
+    assert_eq!(
+        Template::new(&delivery, None).render("{{body}}", false),
+        Err("body".to_string())
+    );
+}
+
+#[test]
+fn headers() {
+    let delivery = delivery();
+    let mut template = Template::new(&delivery, Some(BODY));
+
+    // The header names are case-insensitive.
+    assert_eq!(
+        template.render("{{headers.x-tenant}}", false).unwrap(),
+        "acme; rm -rf /"
+    );
+    assert_eq!(
+        template.render("{{headers.X-ATTEMPT}}", false).unwrap(),
+        "3"
+    );
+    assert_eq!(
+        template.render("{{headers.x-missing}}", false),
+        Err("headers.x-missing".to_string())
+    );
+    assert_eq!(
+        template.render("{{reply_to}}", false),
+        Err("reply_to".to_string())
+    );
+    assert_eq!(
+        template.render("{{unknown}}", false),
+        Err("unknown".to_string())
+    );
+}
+
+#[test]
+fn escape() {
+    let delivery = delivery();
+    let mut template = Template::new(&delivery, Some(BODY));
+
+    assert_eq!(
+        template
+            .render("notify {{headers.x-tenant}} {{body.user.name}}", true)
+            .unwrap(),
+        r#"notify 'acme; rm -rf /' 'O'\''Brien'"#
+    );
+    assert_eq!(
+        template.render("{{routing_key}}", true).unwrap(),
+        "user.created"
+    );
+}
+
+#[test]
+fn uses_body() {
+    assert!(Template::uses_body("echo {{body}}"));
+    assert!(Template::uses_body("echo {{ body.user.name }}"));
+    assert!(!Template::uses_body(
+        "echo {{headers.body}} {{routing_key}}"
+    ));
+    assert!(!Template::uses_body("echo body"));
+}
@@ -0,0 +1,147 @@
+use std::fs;
+
+use tempfile::tempdir;
+
+use serde_json::{json, Value};
+
+use rabbitmq_consumer_lib::client::consumer::output::{Capture, OutputLog};
+use rabbitmq_consumer_lib::client::consumer::worker::{Worker, WorkerError};
+use rabbitmq_consumer_lib::config::queue::config::QueueConfig;
+use rabbitmq_consumer_lib::config::queue::outcome::Outcome;
+
+mod common;
+
+const TIMEOUT: u64 = 5000;
+
+fn queue() -> QueueConfig {
+    common::queue("shell = true\nhandler = \"worker\"")
+}
+
+fn spawn(cmd: &str) -> Worker {
+    Worker::spawn(cmd, &queue(), Capture::new("example#0", 1024, None)).unwrap()
+}
+
+#[tokio::test]
+async fn exchange() {
+    let mut worker = spawn(
+        r#"while read -r frame; do case "$frame" in *later*) echo '{"id":2,"outcome":"retry","output":"busy"}';; *) echo ack;; esac; done"#,
+    );
+
+    let response = worker.send("now", &[], TIMEOUT).await.unwrap();
+    assert_eq!(response.outcome, Outcome::Ack);
+    assert!(response.output.is_empty());
+
+    let response = worker
+        .send(
+            "later",
+            &[("AMQP_ROUTING_KEY".into(), "example".into())],
+            TIMEOUT,
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.outcome, Outcome::Retry);
+    assert_eq!(response.output, "busy");
+
+    // The same process answers every message.
+    let id = worker.id();
+    worker.send("again", &[], TIMEOUT).await.unwrap();
+    assert_eq!(worker.id(), id);
+}
+
+#[tokio::test]
+async fn frame() {
+    // The worker answers with the received frame as output.
+    let mut worker = spawn(r#"read -r frame; printf '{"outcome":"ack","output":%s}\n' "$frame""#);
+
+    let response = worker
+        .send(
+            "{\"text\":\"line\\nbreak\"}",
+            &[("AMQP_QUEUE".into(), "example".into())],
+            TIMEOUT,
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.outcome, Outcome::Ack);
+
+    let frame = serde_json::from_str::<Value>(&response.output).unwrap();
+    assert_eq!(
+        frame,
+        json!({
+            "id": 1,
+            "body": "{\"text\":\"line\\nbreak\"}",
+            "variables": { "AMQP_QUEUE": "example" },
+        })
+    );
+}
+
+#[tokio::test]
+async fn errors() {
+    let mut worker = spawn("read -r frame; exit 3");
+    assert!(matches!(
+        worker.send("body", &[], TIMEOUT).await,
+        Err(WorkerError::Crashed(_))
+    ));
+
+    let mut worker = spawn("read -r frame; echo maybe");
+    assert!(matches!(
+        worker.send("body", &[], TIMEOUT).await,
+        Err(WorkerError::InvalidFrame(_))
+    ));
+
+    let mut worker = spawn("read -r frame; echo '{\"id\":7,\"outcome\":\"ack\"}'");
+    assert!(matches!(
+        worker.send("body", &[], TIMEOUT).await,
+        Err(WorkerError::InvalidFrame(_))
+    ));
+
+    let mut worker = spawn("read -r frame; sleep 5");
+    assert!(matches!(
+        worker.send("body", &[], 100).await,
+        Err(WorkerError::Timeout)
+    ));
+}
+
+#[tokio::test]
+async fn stderr() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("example.log");
+
+    let mut worker = Worker::spawn(
+        "read -r frame; echo failure >&2; echo ack; read -r frame",
+        &queue(),
+        Capture::new(
+            "example#0",
+            1024,
+            Some(OutputLog::open(&path, 1024).unwrap()),
+        ),
+    )
+    .unwrap();
+    assert_eq!(
+        worker.send("body", &[], TIMEOUT).await.unwrap().outcome,
+        Outcome::Ack
+    );
+    worker.stop(TIMEOUT).await;
+
+    // The standard error of the worker is drained to the output log.
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    let log = fs::read_to_string(&path).unwrap();
+    let records = log.lines().collect::<Vec<&str>>();
+    assert!(records[0].ends_with(
+        " [example#0] worker: read -r frame; echo failure >&2; echo ack; read -r frame"
+    ));
+    assert!(records[1].ends_with(" [example#0] stderr: failure"));
+}
+
+#[tokio::test]
+async fn stop() {
+    // The whole process group is terminated, the worker exits within the grace period.
+    let mut worker =
+        spawn("trap 'exit 0' TERM; read -r frame; echo ack; while true; do sleep 0.1; done");
+    worker.send("ready", &[], TIMEOUT).await.unwrap();
+    assert!(!worker.stop(TIMEOUT).await);
+
+    // The signal is ignored by the worker and its children, so they are killed.
+    let mut worker = spawn("trap '' TERM; read -r frame; echo ack; while true; do sleep 0.1; done");
+    worker.send("ready", &[], TIMEOUT).await.unwrap();
+    assert!(worker.stop(200).await);
+}